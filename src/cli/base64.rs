@@ -1,38 +1,88 @@
-use std::str::FromStr;
+use std::{io::Write, path::PathBuf, str::FromStr};
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::{CmdExecutor, input_reader, process_decode, process_encode};
+use crate::{
+    CmdExecutor, EncodeOptions, Input, InputArgs, OCTET_STREAM, content_type_from_mime,
+    guess_content_type, output_writer, parse_data_uri, process_data_uri, process_decode,
+    process_decode_stream, process_detect, process_encode, process_encode_stream,
+    sniff_content_type, write_output,
+};
 
-use super::parse_base64_format;
+use super::parse_encoding;
 
 #[derive(Debug, Clone, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum Base64Subcommand {
-    #[command(name = "encode", about = "Encode a string to base64")]
+    #[command(
+        name = "encode",
+        about = "Encode data as base64, base32, base58, ascii85, z85 or hex"
+    )]
     Encode(EncodeOpts),
-    #[command(name = "decode", about = "Decode a base64 string")]
+    #[command(
+        name = "decode",
+        about = "Decode base64, base32, base58, ascii85, z85 or hex data"
+    )]
     Decode(DecodeOpts),
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct EncodeOpts {
-    #[arg(short, long, value_parser = input_reader, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = parse_base64_format, default_value = "standard")]
-    pub format: Base64Format,
+    #[command(flatten)]
+    pub input: InputArgs,
+    /// standard, url, base32, base32hex, base58, ascii85, z85 or hex
+    #[arg(short, long, value_parser = parse_encoding, default_value = "standard")]
+    pub format: Encoding,
+    /// Strip leading and trailing whitespace from the input
+    #[arg(long, default_value_t = false)]
+    pub trim: bool,
+    /// Pad the output with `=` (base64 and base32 only)
+    #[arg(long, default_value_t = false, conflicts_with = "no_pad")]
+    pub pad: bool,
+    /// Leave out the `=` padding (base64 and base32 only)
+    #[arg(long, default_value_t = false)]
+    pub no_pad: bool,
+    /// Break the output into lines of this many characters
+    #[arg(short, long)]
+    pub wrap: Option<usize>,
+    /// MIME style output: padded and wrapped at 76 characters with CRLF line endings
+    #[arg(long, default_value_t = false, conflicts_with_all = ["wrap", "no_pad"])]
+    pub mime: bool,
+    /// Write the encoded text to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Output a `data:` URI, the MIME type is guessed from the file extension or content
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["format", "pad", "no_pad", "wrap", "mime"]
+    )]
+    pub data_uri: bool,
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct DecodeOpts {
-    #[arg(short, long, value_parser = input_reader, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = parse_base64_format, default_value = "standard")]
-    pub format: Base64Format,
+    #[command(flatten)]
+    pub input: InputArgs,
+    /// standard, url, base32, base32hex, base58, ascii85, z85 or hex
+    #[arg(short, long, value_parser = parse_encoding, default_value = "standard")]
+    pub format: Encoding,
+    /// Write the decoded bytes to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Try the known encodings and report the one that matched
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    pub detect: bool,
+    /// Report the content type of the decoded bytes, e.g. image/png
+    #[arg(long, default_value_t = false)]
+    pub sniff: bool,
+    /// Decode a `data:` URI, an output file without extension gets one matching its MIME type
+    #[arg(long, default_value_t = false, conflicts_with_all = ["format", "detect"])]
+    pub data_uri: bool,
 }
 
-#[derive(Debug, Clone, Parser)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
 pub enum Base64Format {
     Standard,
     UrlSafe,
@@ -59,18 +109,146 @@ impl From<Base64Format> for &'static str {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Base64(Base64Format),
+    Base32,
+    Base32Hex,
+    Base58,
+    Ascii85,
+    Z85,
+    Hex,
+}
+
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" | "base64" => Ok(Encoding::Base64(Base64Format::Standard)),
+            "url" | "base64url" => Ok(Encoding::Base64(Base64Format::UrlSafe)),
+            "base32" => Ok(Encoding::Base32),
+            "base32hex" => Ok(Encoding::Base32Hex),
+            "base58" => Ok(Encoding::Base58),
+            "ascii85" | "base85" => Ok(Encoding::Ascii85),
+            "z85" => Ok(Encoding::Z85),
+            "hex" | "base16" => Ok(Encoding::Hex),
+            _ => Err(anyhow::anyhow!("Invalid encoding: {}", s)),
+        }
+    }
+}
+
+impl From<Encoding> for &'static str {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Base64(format) => format.into(),
+            Encoding::Base32 => "base32",
+            Encoding::Base32Hex => "base32hex",
+            Encoding::Base58 => "base58",
+            Encoding::Ascii85 => "ascii85",
+            Encoding::Z85 => "z85",
+            Encoding::Hex => "hex",
+        }
+    }
+}
+
+impl EncodeOpts {
+    fn options(&self) -> EncodeOptions {
+        if self.mime {
+            return EncodeOptions {
+                padding: Some(true),
+                wrap: Some(76),
+                crlf: true,
+            };
+        }
+        let padding = match (self.pad, self.no_pad) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        EncodeOptions {
+            padding,
+            wrap: self.wrap,
+            crlf: false,
+        }
+    }
+}
+
 impl CmdExecutor for EncodeOpts {
     async fn execute(&self) -> Result<(), anyhow::Error> {
-        let encoded = process_encode(&self.input, &self.format)?;
-        println!("{}", encoded);
+        let mut writer = output_writer(self.output.as_deref())?;
+        match self.format {
+            _ if self.data_uri => {
+                let input = self.input.source();
+                let content = input.read(self.trim)?;
+                let path = match &input {
+                    Input::File(path) => Some(path.as_path()),
+                    _ => None,
+                };
+                let uri = process_data_uri(&content, guess_content_type(path, &content));
+                writer.write_all(uri.as_bytes())?;
+            }
+            // base64 is streamed, trimming needs the whole input though
+            Encoding::Base64(format) if !self.trim => {
+                let reader = self.input.source().reader()?;
+                process_encode_stream(reader, &mut writer, format, &self.options())?;
+            }
+            encoding => {
+                let content = self.input.source().read(self.trim)?;
+                let encoded = process_encode(&content, encoding, &self.options())?;
+                writer.write_all(encoded.as_bytes())?;
+            }
+        }
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl DecodeOpts {
+    fn decode_data_uri(&self) -> anyhow::Result<()> {
+        let (mime, decoded) = parse_data_uri(&self.input.source().read(false)?)?;
+        if self.sniff {
+            eprintln!("Content type: {}", mime);
+        }
+        let output = self.output.as_ref().map(|output| {
+            if output.extension().is_some() {
+                return output.clone();
+            }
+            let content_type = content_type_from_mime(&mime).unwrap_or(OCTET_STREAM);
+            output.with_extension(content_type.extension)
+        });
+        write_output(output.as_deref(), &decoded)?;
+        if let Some(output) = output {
+            eprintln!("Wrote {} to {}", mime, output.display());
+        }
         Ok(())
     }
 }
 
 impl CmdExecutor for DecodeOpts {
     async fn execute(&self) -> Result<(), anyhow::Error> {
-        let encoded = process_decode(&self.input, &self.format)?;
-        println!("{}", encoded);
+        if self.data_uri {
+            return self.decode_data_uri();
+        }
+        if let (Encoding::Base64(format), false, false) = (self.format, self.detect, self.sniff) {
+            let mut writer = output_writer(self.output.as_deref())?;
+            process_decode_stream(self.input.source().reader()?, &mut writer, format)?;
+            return Ok(());
+        }
+
+        let content = self.input.source().read(false)?;
+        let decoded = if self.detect {
+            let (encoding, decoded) = process_detect(&content)?;
+            eprintln!("Detected encoding: {}", <&str>::from(encoding));
+            decoded
+        } else {
+            process_decode(&content, self.format)?
+        };
+        if self.sniff {
+            eprintln!("Content type: {}", sniff_content_type(&decoded));
+        }
+        write_output(self.output.as_deref(), &decoded)?;
         Ok(())
     }
 }
@@ -1,16 +1,27 @@
-use std::{fs, path::PathBuf, str::FromStr};
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
 use crate::{
-    CmdExecutor, input_reader, process_decrypt, process_encrypt, process_generate, verify_path,
+    CmdExecutor, InputArgs, KeyArgs, SignatureFormat, process_decrypt, process_encrypt,
+    process_generate, process_key_info, process_key_pub, read_password, seal_key,
+    verify_input_file, verify_path, write_key_file,
 };
 
 #[derive(Debug, Clone)]
 pub enum EncryptFormat {
     Blake3,
     Ed25519,
+    HmacSha256,
+    HmacSha512,
+    EcdsaP256,
+    RsaPss,
+    RsaPkcs1,
 }
 
 fn parse_format(format: &str) -> Result<EncryptFormat, anyhow::Error> {
@@ -24,40 +35,110 @@ impl FromStr for EncryptFormat {
         match s {
             "blake3" => Ok(EncryptFormat::Blake3),
             "ed25519" => Ok(EncryptFormat::Ed25519),
+            "hmac-sha256" => Ok(EncryptFormat::HmacSha256),
+            "hmac-sha512" => Ok(EncryptFormat::HmacSha512),
+            "p256" | "ecdsa-p256" => Ok(EncryptFormat::EcdsaP256),
+            "rsa-pss" => Ok(EncryptFormat::RsaPss),
+            "rsa-pkcs1" => Ok(EncryptFormat::RsaPkcs1),
             _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
         }
     }
 }
 
+impl From<&EncryptFormat> for &'static str {
+    fn from(format: &EncryptFormat) -> Self {
+        match format {
+            EncryptFormat::Blake3 => "blake3",
+            EncryptFormat::Ed25519 => "ed25519",
+            EncryptFormat::HmacSha256 => "hmac-sha256",
+            EncryptFormat::HmacSha512 => "hmac-sha512",
+            EncryptFormat::EcdsaP256 => "p256",
+            EncryptFormat::RsaPss => "rsa-pss",
+            EncryptFormat::RsaPkcs1 => "rsa-pkcs1",
+        }
+    }
+}
+
+impl EncryptFormat {
+    /// File names `crypto generate` uses for the secret and, for asymmetric
+    /// algorithms, the public key.
+    fn key_file_names(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            EncryptFormat::Blake3 => ("blake3.txt", None),
+            EncryptFormat::Ed25519 => ("ed25519.sk", Some("ed25519.pk")),
+            EncryptFormat::HmacSha256 => ("hmac-sha256.txt", None),
+            EncryptFormat::HmacSha512 => ("hmac-sha512.txt", None),
+            EncryptFormat::EcdsaP256 => ("p256.sk", Some("p256.pk")),
+            EncryptFormat::RsaPss | EncryptFormat::RsaPkcs1 => ("rsa.sk", Some("rsa.pk")),
+        }
+    }
+}
+
+fn parse_sig_format(format: &str) -> Result<SignatureFormat, anyhow::Error> {
+    format.parse()
+}
+
 #[derive(Debug, Clone, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum CryptoSubcommand {
-    #[command(about = "Encrypt a message with a private/shared key.")]
+    #[command(alias = "sign", about = "Sign a message with a private/shared key.")]
     Encrypt(EncryptOpts),
-    #[command(about = "Decrypt a message with a private/shared key.")]
+    #[command(
+        alias = "verify",
+        about = "Verify a message signature with a public/shared key."
+    )]
     Decrypt(DecryptOpts),
     #[command(about = "Generate a new key")]
     Generate(GenerateKeyOpts),
+    #[command(subcommand, about = "Inspect key files")]
+    Key(KeySubcommand),
+}
+
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum KeySubcommand {
+    #[command(about = "Show algorithm, type and fingerprint of a key file")]
+    Info(KeyInfoOpts),
+    #[command(name = "pub", about = "Derive the public key from a secret key")]
+    Pub(KeyPubOpts),
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct EncryptOpts {
-    #[arg(short, long, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = input_reader)]
-    pub key: String,
+    #[command(flatten)]
+    pub input: InputArgs,
+    /// Strip leading and trailing whitespace from the input before signing
+    #[arg(long, default_value_t = false)]
+    pub trim: bool,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Passphrase protecting the key file
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
     #[arg(short, long, value_parser = parse_format, default_value = "blake3")]
     pub format: EncryptFormat,
+    /// Encoding of the signature: base64url, base64 or hex
+    #[arg(long, value_parser = parse_sig_format, default_value = "base64url")]
+    pub sig_format: SignatureFormat,
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct DecryptOpts {
-    #[arg(short, long, value_parser = input_reader, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = input_reader)]
-    pub key: String,
+    #[command(flatten)]
+    pub input: InputArgs,
+    /// Strip leading and trailing whitespace from the input before verifying
+    #[arg(long, default_value_t = false)]
+    pub trim: bool,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Passphrase protecting the key file
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
     #[arg(short, long)]
     pub sig: String,
+    /// Encoding of the signature: base64url, base64 or hex
+    #[arg(long, value_parser = parse_sig_format, default_value = "base64url")]
+    pub sig_format: SignatureFormat,
     #[arg(short, long, value_parser = parse_format, default_value = "blake3")]
     pub format: EncryptFormat,
 }
@@ -68,40 +149,156 @@ pub struct GenerateKeyOpts {
     pub format: EncryptFormat,
     #[arg(short, long, value_parser = verify_path)]
     pub output: PathBuf,
+    /// Encrypt the secret key on disk, prompts for the passphrase when given
+    /// without a value
+    #[arg(
+        long,
+        env = "RCLI_KEY_PASSPHRASE",
+        hide_env_values = true,
+        num_args = 0..=1,
+        default_missing_value = ""
+    )]
+    pub passphrase: Option<String>,
+    /// Overwrite existing key files
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct KeyInfoOpts {
+    #[arg(value_parser = verify_input_file)]
+    pub file: String,
+    #[arg(short, long, value_parser = parse_format, default_value = "ed25519")]
+    pub format: EncryptFormat,
+    /// Passphrase protecting the key file
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct KeyPubOpts {
+    #[arg(value_parser = verify_input_file)]
+    pub file: String,
+    #[arg(short, long, value_parser = parse_format, default_value = "ed25519")]
+    pub format: EncryptFormat,
+    /// Where to write the public key, defaults to the secret key path with a `.pk` extension
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Passphrase protecting the key file
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+    /// Overwrite an existing public key file
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
 }
 
 impl CmdExecutor for EncryptOpts {
     async fn execute(&self) -> anyhow::Result<()> {
-        let encoded = process_encrypt(&self.input, &self.format, &self.key)?;
-        println!("{:?}", encoded);
+        let encoded = process_encrypt(
+            &self.input.source().read(self.trim)?,
+            &self.format,
+            &self.key.source(),
+            self.passphrase.as_deref(),
+        )?;
+        println!("{}", self.sig_format.encode(&encoded));
         Ok(())
     }
 }
 
 impl CmdExecutor for DecryptOpts {
     async fn execute(&self) -> anyhow::Result<()> {
-        let decoded = process_decrypt(&self.input, &self.format, &self.key, &self.sig)?;
+        let decoded = process_decrypt(
+            &self.input.source().read(self.trim)?,
+            &self.format,
+            &self.key.source(),
+            self.passphrase.as_deref(),
+            &self.sig,
+            &self.sig_format,
+        )?;
         println!("{:?}", decoded);
         Ok(())
     }
 }
 
+impl GenerateKeyOpts {
+    /// The passphrase from the environment, or asked for so it stays out of
+    /// the process list and shell history.
+    fn passphrase(&self) -> anyhow::Result<Option<String>> {
+        match self.passphrase.as_deref() {
+            Some("") => {
+                let passphrase = read_password("Passphrase: ")?;
+                if passphrase.is_empty() {
+                    anyhow::bail!("Passphrase must not be empty");
+                }
+                if io::stdin().is_terminal() && read_password("Repeat passphrase: ")? != passphrase
+                {
+                    anyhow::bail!("Passphrases do not match");
+                }
+                Ok(Some(passphrase))
+            }
+            passphrase => Ok(passphrase.map(String::from)),
+        }
+    }
+}
+
 impl CmdExecutor for GenerateKeyOpts {
     async fn execute(&self) -> anyhow::Result<()> {
         let key = process_generate(&self.format)?;
+        let secret = match &self.passphrase()? {
+            Some(passphrase) => seal_key(&key[0], passphrase)?,
+            None => key[0].clone(),
+        };
 
-        match self.format {
-            EncryptFormat::Blake3 => {
-                let path = self.output.join("blake3.txt");
-                fs::write(path, &key[0])?;
-            }
-            EncryptFormat::Ed25519 => {
-                let path = self.output.clone();
-                fs::write(path.join("ed25519.sk"), &key[0])?;
-                fs::write(path.join("ed25519.pk"), &key[1])?;
+        let (sk_name, pk_name) = self.format.key_file_names();
+        let mut files = vec![(self.output.join(sk_name), &secret, true)];
+        if let (Some(pk_name), Some(pk)) = (pk_name, key.get(1)) {
+            files.push((self.output.join(pk_name), pk, false));
+        }
+        // check every destination first so a half written pair never ends up on disk
+        if !self.force
+            && let Some((path, _, _)) = files.iter().find(|(path, _, _)| path.exists())
+        {
+            anyhow::bail!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            );
+        }
+        let mut written = Vec::new();
+        for (path, content, secret) in files {
+            if let Err(e) = write_key_file(&path, content, secret, self.force) {
+                if !self.force {
+                    for path in &written {
+                        let _ = std::fs::remove_file(path);
+                    }
+                }
+                return Err(e);
             }
+            written.push(path);
         }
-        println!("{:?}", key);
+        for path in written {
+            println!("Key written to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for KeyInfoOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let info = process_key_info(&self.file, &self.format, self.passphrase.as_deref())?;
+        println!("{}", info);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for KeyPubOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let pk = process_key_pub(&self.file, &self.format, self.passphrase.as_deref())?;
+        let output = match &self.output {
+            Some(output) => output.clone(),
+            None => PathBuf::from(&self.file).with_extension("pk"),
+        };
+        write_key_file(&output, &pk, false, self.force)?;
+        println!("Public key written to {}", output.display());
         Ok(())
     }
 }
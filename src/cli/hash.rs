@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use clap::Parser;
+
+use crate::{CheckStatus, CmdExecutor, process_check, process_hash, verify_input_file};
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashAlgo {
+    Blake3,
+    Sha256,
+    Sha512,
+    Sha1,
+    Md5,
+    Xxh3,
+}
+
+impl HashAlgo {
+    /// Length of the hex digest.
+    pub fn hex_len(&self) -> usize {
+        match self {
+            HashAlgo::Blake3 | HashAlgo::Sha256 => 64,
+            HashAlgo::Sha512 => 128,
+            HashAlgo::Sha1 => 40,
+            HashAlgo::Md5 => 32,
+            HashAlgo::Xxh3 => 16,
+        }
+    }
+
+    /// The algorithms a hex digest of `len` characters may come from, most
+    /// likely first. 64 characters is our own blake3 default or SHA-256.
+    pub fn from_hex_len(len: usize) -> &'static [Self] {
+        match len {
+            64 => &[HashAlgo::Blake3, HashAlgo::Sha256],
+            128 => &[HashAlgo::Sha512],
+            40 => &[HashAlgo::Sha1],
+            32 => &[HashAlgo::Md5],
+            16 => &[HashAlgo::Xxh3],
+            _ => &[],
+        }
+    }
+}
+
+fn parse_hash_algo(algo: &str) -> Result<HashAlgo, anyhow::Error> {
+    algo.parse()
+}
+
+impl FromStr for HashAlgo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(HashAlgo::Blake3),
+            "sha256" => Ok(HashAlgo::Sha256),
+            "sha512" => Ok(HashAlgo::Sha512),
+            "sha1" => Ok(HashAlgo::Sha1),
+            "md5" => Ok(HashAlgo::Md5),
+            "xxh3" => Ok(HashAlgo::Xxh3),
+            _ => Err(anyhow::anyhow!("Invalid hash algorithm: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct HashOpts {
+    /// Defaults to blake3, with --check to what the digest length allows
+    #[arg(short, long, value_parser = parse_hash_algo)]
+    pub algo: Option<HashAlgo>,
+    /// Read checksums from the file and check them
+    #[arg(short, long, value_parser = verify_input_file, conflicts_with = "files")]
+    pub check: Option<String>,
+    /// Files or directories to hash, `-` for stdin
+    #[arg(default_value = "-")]
+    pub files: Vec<String>,
+}
+
+impl CmdExecutor for HashOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        match &self.check {
+            Some(checksums) => {
+                let results = process_check(checksums, self.algo)?;
+                let failed = results
+                    .iter()
+                    .filter(|(_, status)| *status != CheckStatus::Ok)
+                    .count();
+                for (path, status) in results {
+                    println!("{}: {}", path, status);
+                }
+                if failed > 0 {
+                    return Err(anyhow::anyhow!(
+                        "{} computed checksum(s) did NOT match",
+                        failed
+                    ));
+                }
+            }
+            None => {
+                let mut failed = 0;
+                for (path, digest) in
+                    process_hash(&self.files, self.algo.unwrap_or(HashAlgo::Blake3))?
+                {
+                    match digest {
+                        Ok(digest) => println!("{}  {}", digest, path),
+                        Err(e) => {
+                            failed += 1;
+                            eprintln!("{}: {}", path, e);
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(anyhow::anyhow!("{} file(s) could not be read", failed));
+                }
+            }
+        }
+        Ok(())
+    }
+}
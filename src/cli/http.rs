@@ -1,15 +1,24 @@
-use std::path::PathBuf;
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::{CmdExecutor, process_http, verify_path};
+use crate::{
+    CmdExecutor, Ed25519Verifier, HttpAuth, HttpOptions, KeyArgs, KeyLoader, TlsConfig,
+    process_http, process_http_sign, verify_input_file, verify_path,
+};
 
 #[derive(Debug, Clone, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum HttpSubCommand {
     #[command(about = "Serve a directory over HTTP")]
     Serve(HttpServeOpts),
+    #[command(about = "Sign an expiring link that needs no other credentials")]
+    Sign(HttpSignOpts),
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -18,10 +27,180 @@ pub struct HttpServeOpts {
     pub dir: PathBuf,
     #[arg(short, long, default_value_t = 8080)]
     pub port: u16,
+    /// Address to listen on, e.g. 127.0.0.1 or :: for IPv6
+    #[arg(short, long, default_value = "0.0.0.0")]
+    pub bind: IpAddr,
+    /// Serve symlinks that point outside the served directory
+    #[arg(long, default_value_t = false)]
+    pub follow_symlinks: bool,
+    /// Serve and list dotfiles such as `.git` or `.env`
+    #[arg(long, default_value_t = false)]
+    pub show_hidden: bool,
+    /// PEM certificate chain to serve HTTPS with
+    #[arg(long, value_parser = verify_input_file, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+    /// PEM private key for --tls-cert
+    #[arg(long, value_parser = verify_input_file, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+    /// Serve HTTPS with a certificate generated on startup
+    #[arg(long, default_value_t = false, conflicts_with = "tls_cert")]
+    pub self_signed: bool,
+    /// Names the self-signed certificate is valid for
+    #[arg(
+        long = "san",
+        value_delimiter = ',',
+        default_value = "localhost,127.0.0.1,::1",
+        requires = "self_signed"
+    )]
+    pub sans: Vec<String>,
+    /// Accept file uploads with multipart POST or PUT
+    #[arg(long, default_value_t = false)]
+    pub upload: bool,
+    /// Largest accepted upload, e.g. 512K, 100M or 2G
+    #[arg(long, value_parser = parse_size, default_value = "100M", requires = "upload")]
+    pub max_upload_size: u64,
+    /// Let uploads replace existing files
+    #[arg(long, default_value_t = false, requires = "upload")]
+    pub overwrite: bool,
+    /// Require basic auth with this `user:password`, may be repeated
+    #[arg(long = "auth", value_parser = parse_user)]
+    pub users: Vec<(String, String)>,
+    /// Require basic auth against a file of `user:bcrypt-hash` lines
+    #[arg(long, value_parser = verify_input_file)]
+    pub htpasswd: Option<String>,
+    /// Require this bearer token, may be repeated
+    #[arg(long = "token", env = "RCLI_HTTP_TOKEN", hide_env_values = true)]
+    pub tokens: Vec<String>,
+    /// Ed25519 public key accepting links made with `rcli http sign`
+    #[arg(long, value_parser = verify_input_file)]
+    pub url_key: Option<String>,
+    /// Origins allowed to make cross-origin requests, `*` for any
+    #[arg(long, value_delimiter = ',', value_parser = parse_origin)]
+    pub cors: Vec<String>,
+    /// Don't compress responses, even when the client accepts it
+    #[arg(long, default_value_t = false)]
+    pub no_compress: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct HttpSignOpts {
+    /// Path of the file or directory below the served directory
+    pub path: String,
+    /// Ed25519 secret key
+    #[command(flatten)]
+    pub key: KeyArgs,
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+    /// How long the link stays valid, e.g. `30m` or `7d`
+    #[arg(long, value_parser = parse_duration, default_value = "1h")]
+    pub expires: Duration,
+    /// Prefix for the link, e.g. `https://example.com:8080`
+    #[arg(long)]
+    pub base_url: Option<String>,
+}
+
+fn parse_user(user: &str) -> Result<(String, String), &'static str> {
+    user.split_once(':')
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, password)| (name.to_string(), password.to_string()))
+        .ok_or("Credentials must be in the form user:password")
+}
+
+/// `*` or a `scheme://host[:port]` origin, as browsers send it.
+fn parse_origin(origin: &str) -> Result<String, &'static str> {
+    let origin = origin.trim().trim_end_matches('/');
+    let host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    match host {
+        _ if origin == "*" => Ok(origin.to_string()),
+        Some(host) if !host.is_empty() && !host.contains(['/', '?', '#', ' ']) => {
+            Ok(origin.to_string())
+        }
+        _ => Err("Origin must be * or like https://example.com[:port]"),
+    }
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, humantime::DurationError> {
+    humantime::parse_duration(duration)
+}
+
+/// A byte count with an optional binary K, M or G suffix.
+fn parse_size(size: &str) -> Result<u64, anyhow::Error> {
+    let size = size.trim();
+    let (number, shift) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 30),
+        _ => (size, 0),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {}", size))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| anyhow::anyhow!("Size too large: {}", size))
+}
+
+impl HttpServeOpts {
+    fn tls(&self) -> Option<TlsConfig> {
+        if self.self_signed {
+            return Some(TlsConfig::SelfSigned(self.sans.clone()));
+        }
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsConfig::Files {
+                cert: cert.into(),
+                key: key.into(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn auth(&self) -> anyhow::Result<HttpAuth> {
+        let mut auth = HttpAuth::default();
+        for (name, password) in &self.users {
+            auth = auth.user(name, password);
+        }
+        if let Some(htpasswd) = &self.htpasswd {
+            auth = auth.htpasswd(htpasswd)?;
+        }
+        for token in &self.tokens {
+            auth = auth.token(token);
+        }
+        if let Some(key) = &self.url_key {
+            auth = auth.url_key(Ed25519Verifier::load(key)?);
+        }
+        Ok(auth)
+    }
 }
 
 impl CmdExecutor for HttpServeOpts {
     async fn execute(&self) -> anyhow::Result<()> {
-        process_http(self.dir.clone(), self.port).await
+        let options = HttpOptions {
+            follow_symlinks: self.follow_symlinks,
+            show_hidden: self.show_hidden,
+            upload: self.upload,
+            max_upload_size: self.max_upload_size,
+            overwrite: self.overwrite,
+            auth: self.auth()?,
+            cors: self.cors.clone(),
+            compress: !self.no_compress,
+        };
+        let addr = SocketAddr::new(self.bind, self.port);
+        process_http(self.dir.clone(), addr, options, self.tls()).await
+    }
+}
+
+impl CmdExecutor for HttpSignOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let url = process_http_sign(
+            &self.path,
+            &self.key.source(),
+            self.passphrase.as_deref(),
+            self.expires,
+            self.base_url.as_deref(),
+        )?;
+        println!("{}", url);
+        Ok(())
     }
 }
@@ -0,0 +1,58 @@
+use clap::Args;
+
+use crate::{Input, parse_input};
+
+fn select_source(
+    file: &Option<Input>,
+    text: &Option<String>,
+    env: &Option<String>,
+) -> Option<Input> {
+    match (file, text, env) {
+        (Some(file), _, _) => Some(file.clone()),
+        (_, Some(text), _) => Some(Input::Literal(text.as_bytes().to_vec())),
+        (_, _, Some(var)) => Some(Input::Env(var.clone())),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+#[group(multiple = false)]
+pub struct InputArgs {
+    /// Read input from a file (`@path` or `path`), or `-` for stdin
+    #[arg(short, long, value_parser = parse_input)]
+    pub input: Option<Input>,
+    /// Use this text as input
+    #[arg(long)]
+    pub text: Option<String>,
+    /// Read input from this environment variable
+    #[arg(long)]
+    pub env: Option<String>,
+}
+
+impl InputArgs {
+    /// The selected source, stdin if none was given.
+    pub fn source(&self) -> Input {
+        select_source(&self.input, &self.text, &self.env).unwrap_or(Input::Stdin)
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+#[group(required = true, multiple = false)]
+pub struct KeyArgs {
+    /// Read the key from a file (`@path` or `path`), or `-` for stdin
+    #[arg(short, long, value_parser = parse_input)]
+    pub key: Option<Input>,
+    /// Use this text as the key
+    #[arg(long)]
+    pub key_text: Option<String>,
+    /// Read the key from this environment variable
+    #[arg(long)]
+    pub key_env: Option<String>,
+}
+
+impl KeyArgs {
+    pub fn source(&self) -> Input {
+        // the group is required, so clap guarantees one of them is set
+        select_source(&self.key, &self.key_text, &self.key_env).unwrap_or(Input::Stdin)
+    }
+}
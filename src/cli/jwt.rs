@@ -0,0 +1,166 @@
+use std::{str::FromStr, time::Duration};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    CmdExecutor, JwtClaims, KeyArgs, process_jwt_decode, process_jwt_sign, process_jwt_verify,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlg {
+    HS256,
+    HS512,
+    EdDSA,
+    ES256,
+    RS256,
+    PS256,
+}
+
+fn parse_jwt_alg(alg: &str) -> Result<JwtAlg, anyhow::Error> {
+    alg.parse()
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, humantime::DurationError> {
+    humantime::parse_duration(duration)
+}
+
+fn parse_claim(claim: &str) -> Result<(String, String), &'static str> {
+    claim
+        .split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or("Claim must be in the form key=value")
+}
+
+impl FromStr for JwtAlg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" => Ok(JwtAlg::HS256),
+            "HS512" => Ok(JwtAlg::HS512),
+            "EdDSA" => Ok(JwtAlg::EdDSA),
+            "ES256" => Ok(JwtAlg::ES256),
+            "RS256" => Ok(JwtAlg::RS256),
+            "PS256" => Ok(JwtAlg::PS256),
+            _ => Err(anyhow::anyhow!("Invalid JWT algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<JwtAlg> for &'static str {
+    fn from(alg: JwtAlg) -> Self {
+        match alg {
+            JwtAlg::HS256 => "HS256",
+            JwtAlg::HS512 => "HS512",
+            JwtAlg::EdDSA => "EdDSA",
+            JwtAlg::ES256 => "ES256",
+            JwtAlg::RS256 => "RS256",
+            JwtAlg::PS256 => "PS256",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum JwtSubcommand {
+    #[command(about = "Sign a new JWT")]
+    Sign(JwtSignOpts),
+    #[command(about = "Verify a JWT's signature and registered claims")]
+    Verify(JwtVerifyOpts),
+    #[command(about = "Print a JWT's header and claims without verifying it")]
+    Decode(JwtDecodeOpts),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct JwtSignOpts {
+    #[arg(long)]
+    pub sub: Option<String>,
+    #[arg(long)]
+    pub aud: Option<String>,
+    #[arg(long)]
+    pub iss: Option<String>,
+    /// Lifetime of the token, e.g. `30m` or `14d`
+    #[arg(long, value_parser = parse_duration, default_value = "1h")]
+    pub exp: Duration,
+    /// Additional claims, e.g. `--claim role=admin`
+    #[arg(long = "claim", value_parser = parse_claim)]
+    pub claims: Vec<(String, String)>,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+    #[arg(long, value_parser = parse_jwt_alg, default_value = "HS256")]
+    pub alg: JwtAlg,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct JwtVerifyOpts {
+    pub token: String,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    #[arg(long, env = "RCLI_KEY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+    /// Algorithm the token must be signed with, the header is never trusted
+    #[arg(long, value_parser = parse_jwt_alg)]
+    pub alg: JwtAlg,
+    /// Required audience
+    #[arg(long)]
+    pub aud: Option<String>,
+    /// Required issuer
+    #[arg(long)]
+    pub iss: Option<String>,
+    /// Allowed clock skew when checking `exp` and `nbf`
+    #[arg(long, value_parser = parse_duration, default_value = "60s")]
+    pub leeway: Duration,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct JwtDecodeOpts {
+    pub token: String,
+}
+
+impl CmdExecutor for JwtSignOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let claims = JwtClaims {
+            sub: self.sub.clone(),
+            aud: self.aud.clone(),
+            iss: self.iss.clone(),
+            exp: self.exp,
+            extra: self.claims.clone(),
+        };
+        let token = process_jwt_sign(
+            &claims,
+            self.alg,
+            &self.key.source(),
+            self.passphrase.as_deref(),
+        )?;
+        println!("{}", token);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for JwtVerifyOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let claims = process_jwt_verify(
+            &self.token,
+            self.alg,
+            &self.key.source(),
+            self.passphrase.as_deref(),
+            self.aud.as_deref(),
+            self.iss.as_deref(),
+            self.leeway,
+        )?;
+        println!("{}", serde_json::to_string_pretty(&claims)?);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for JwtDecodeOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let (header, claims) = process_jwt_decode(&self.token)?;
+        println!("{}", serde_json::to_string_pretty(&header)?);
+        println!("{}", serde_json::to_string_pretty(&claims)?);
+        Ok(())
+    }
+}
@@ -1,28 +1,32 @@
 mod base64;
 mod crypto;
 mod csv;
+mod hash;
 mod http;
+mod input;
+mod jwt;
 mod opts;
+mod otp;
 mod passwd;
 
 pub use base64::*;
 pub use crypto::*;
 pub use csv::*;
+pub use hash::*;
 pub use http::*;
+pub use input::*;
+pub use jwt::*;
 pub use opts::*;
+pub use otp::*;
 pub use passwd::*;
 
-pub fn parse_base64_format(format: &str) -> Result<Base64Format, &'static str> {
-    match format {
-        "standard" => Ok(Base64Format::Standard),
-        "url" => Ok(Base64Format::UrlSafe),
-        _ => Err("Invalid base64 format."),
-    }
+pub fn parse_encoding(encoding: &str) -> Result<Encoding, anyhow::Error> {
+    encoding.parse()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::verify_input_file;
+    use crate::{Input, verify_input_file};
 
     #[test]
     fn test_verify_input_file() {
@@ -33,4 +37,30 @@ mod tests {
             Err("File does not exist.")
         );
     }
+
+    #[test]
+    fn test_input_preserves_bytes() -> anyhow::Result<()> {
+        let input = Input::Literal(b"  hello!\n".to_vec());
+        assert_eq!(input.read(false)?, b"  hello!\n");
+        assert_eq!(input.read(true)?, b"hello!");
+
+        let input: Input = "fixtures/ed25519.sk".parse()?;
+        assert_eq!(input.read(false)?, std::fs::read("fixtures/ed25519.sk")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!("-".parse::<Input>().unwrap(), Input::Stdin);
+        assert_eq!(
+            "@Cargo.toml".parse::<Input>().unwrap(),
+            Input::File("Cargo.toml".into())
+        );
+        assert_eq!(
+            "Cargo.toml".parse::<Input>().unwrap(),
+            Input::File("Cargo.toml".into())
+        );
+        // a typo is an error rather than literal content
+        assert!("Cargo.tmol".parse::<Input>().is_err());
+    }
 }
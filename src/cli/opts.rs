@@ -1,7 +1,10 @@
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use super::{Base64Subcommand, CryptoSubcommand, CsvOpts, GenPassOpts, HttpSubCommand};
+use super::{
+    Base64Subcommand, CryptoSubcommand, CsvOpts, DecodeOpts, EncodeOpts, GenPassOpts, HashOpts,
+    HttpSubCommand, JwtSubcommand, OtpSubcommand, PasswdSubcommand,
+};
 
 #[derive(Debug, Parser, Clone)]
 #[enum_dispatch(CmdExecutor)]
@@ -10,12 +13,33 @@ pub enum SubCommand {
     Csv(CsvOpts),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
-    #[command(subcommand, about = "Encode/decode base64")]
+    #[command(subcommand, about = "Check, hash and verify passwords")]
+    Passwd(PasswdSubcommand),
+    #[command(subcommand, about = "Generate and verify TOTP/HOTP one-time passwords")]
+    Otp(OtpSubcommand),
+    #[command(
+        subcommand,
+        about = "Encode/decode base64, base32, base58, ascii85, z85 or hex"
+    )]
     Base64(Base64Subcommand),
+    #[command(
+        name = "encode",
+        about = "Encode data as base64, base32, base58, ascii85, z85 or hex"
+    )]
+    Encode(EncodeOpts),
+    #[command(
+        name = "decode",
+        about = "Decode base64, base32, base58, ascii85, z85 or hex data"
+    )]
+    Decode(DecodeOpts),
     #[command(subcommand, about = "Encrypt/decrypt data")]
     Crypto(CryptoSubcommand),
     #[command(subcommand, about = "HTTP server")]
     Http(HttpSubCommand),
+    #[command(name = "hash", about = "Compute or check file digests")]
+    Hash(HashOpts),
+    #[command(subcommand, about = "Sign/verify/decode JSON Web Tokens")]
+    Jwt(JwtSubcommand),
 }
 
 #[derive(Debug, Parser)]
@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use clap::{Args, Parser};
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    CmdExecutor, OtpParams, process_otp_code, process_otp_secret, process_otp_uri,
+    process_otp_verify, render_qr,
+};
+
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum OtpSubcommand {
+    #[command(about = "Generate a random base32 secret")]
+    Secret(OtpSecretOpts),
+    #[command(about = "Print the current TOTP code, or a HOTP code with --counter")]
+    Code(OtpCodeOpts),
+    #[command(about = "Check a code, allowing for clock skew")]
+    Verify(OtpVerifyOpts),
+    #[command(about = "Print an otpauth:// URI for authenticator apps")]
+    Uri(OtpUriOpts),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgo {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+fn parse_otp_algo(algo: &str) -> Result<OtpAlgo, anyhow::Error> {
+    algo.parse()
+}
+
+impl FromStr for OtpAlgo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgo::Sha1),
+            "sha256" => Ok(OtpAlgo::Sha256),
+            "sha512" => Ok(OtpAlgo::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid OTP algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<OtpAlgo> for &'static str {
+    fn from(algo: OtpAlgo) -> Self {
+        match algo {
+            OtpAlgo::Sha1 => "sha1",
+            OtpAlgo::Sha256 => "sha256",
+            OtpAlgo::Sha512 => "sha512",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct OtpArgs {
+    /// Base32 secret shared with the authenticator
+    #[arg(long, env = "RCLI_OTP_SECRET", hide_env_values = true)]
+    pub secret: String,
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(6..=8))]
+    pub digits: u32,
+    /// TOTP time step in seconds
+    #[arg(long, default_value_t = 30, conflicts_with = "counter")]
+    pub period: u64,
+    /// sha1, sha256 or sha512
+    #[arg(long, value_parser = parse_otp_algo, default_value = "sha1")]
+    pub algo: OtpAlgo,
+    /// Use counter based HOTP instead of TOTP
+    #[arg(long)]
+    pub counter: Option<u64>,
+}
+
+impl OtpArgs {
+    fn params(&self) -> anyhow::Result<OtpParams> {
+        OtpParams::try_new(&self.secret, self.algo, self.digits, self.period)
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct OtpSecretOpts {
+    /// Secret length in bytes, 20 matches the SHA1 output size
+    #[arg(short, long, default_value_t = 20, value_parser = clap::value_parser!(u16).range(10..=64))]
+    pub bytes: u16,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct OtpCodeOpts {
+    #[command(flatten)]
+    pub otp: OtpArgs,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct OtpVerifyOpts {
+    pub code: String,
+    #[command(flatten)]
+    pub otp: OtpArgs,
+    /// Accept codes this many time steps (or HOTP counters ahead) off
+    #[arg(long, default_value_t = 1)]
+    pub skew: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct OtpUriOpts {
+    #[command(flatten)]
+    pub otp: OtpArgs,
+    /// Service name shown in the authenticator
+    #[arg(long)]
+    pub issuer: Option<String>,
+    /// Account name, usually the user's email
+    #[arg(long)]
+    pub account: String,
+    /// Also render the URI as a QR code in the terminal
+    #[arg(long, default_value_t = false)]
+    pub qr: bool,
+}
+
+impl CmdExecutor for OtpSecretOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        println!("{}", process_otp_secret(self.bytes as usize)?);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for OtpCodeOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let (code, valid_for) = process_otp_code(&self.otp.params()?, self.otp.counter)?;
+        println!("{}", code);
+        if let Some(secs) = valid_for {
+            eprintln!("Valid for {}s", secs);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for OtpVerifyOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let params = self.otp.params()?;
+        match process_otp_verify(&params, &self.code, self.otp.counter, self.skew)? {
+            Some(offset) => {
+                match self.otp.counter {
+                    Some(counter) => {
+                        eprintln!("Valid, next counter is {}", counter + offset as u64 + 1)
+                    }
+                    None if offset != 0 => eprintln!("Valid ({:+} steps)", offset),
+                    None => eprintln!("Valid"),
+                }
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("Invalid code")),
+        }
+    }
+}
+
+impl CmdExecutor for OtpUriOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let uri = process_otp_uri(
+            &self.otp.params()?,
+            self.issuer.as_deref(),
+            &self.account,
+            self.otp.counter,
+        )?;
+        if self.qr {
+            println!("{}", render_qr(&uri)?);
+        }
+        println!("{}", uri);
+        Ok(())
+    }
+}
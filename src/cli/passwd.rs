@@ -1,6 +1,26 @@
+use std::{path::PathBuf, str::FromStr};
+
 use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::{
+    CmdExecutor, PasswdPolicy, format_passwd_records, hash_passwd, load_wordlist,
+    passphrase_entropy, process_passphrase_with_rng, process_passwd_check, process_passwd_records,
+    process_passwd_with_rng, read_password, verify_input_file, verify_passwd,
+};
 
-use crate::{CmdExecutor, process_passwd};
+#[derive(Debug, Clone, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum PasswdSubcommand {
+    #[command(about = "Estimate the strength of a password read from a prompt or stdin")]
+    Check(CheckOpts),
+    #[command(about = "Hash a password read from a prompt or stdin")]
+    Hash(HashPasswdOpts),
+    #[command(about = "Check a password read from a prompt or stdin against a hash")]
+    Verify(VerifyPasswdOpts),
+}
 
 #[derive(Debug, Clone, Parser)]
 pub struct GenPassOpts {
@@ -14,18 +34,290 @@ pub struct GenPassOpts {
     pub number: bool,
     #[arg(short, long, action = clap::ArgAction::Set, default_value_t = true)]
     pub symbol: bool,
+    /// Pick characters from this set only, instead of the built in ones
+    #[arg(long)]
+    pub charset: Option<String>,
+    /// Symbols to use instead of the built in `!@#$%^&*_`
+    #[arg(long, conflicts_with = "charset")]
+    pub symbols: Option<String>,
+    /// Characters that must not appear, e.g. "il1O0"
+    #[arg(long, default_value = "")]
+    pub exclude: String,
+    #[arg(long, default_value_t = 0)]
+    pub min_upper: usize,
+    #[arg(long, default_value_t = 0)]
+    pub min_lower: usize,
+    #[arg(long, default_value_t = 0)]
+    pub min_digits: usize,
+    #[arg(long, default_value_t = 0)]
+    pub min_symbols: usize,
+    /// Never put the same character twice in a row
+    #[arg(long, default_value_t = false)]
+    pub no_repeat: bool,
+    /// Read the rules above from a YAML policy file
+    #[arg(long, conflicts_with_all = [
+        "length", "uppercase", "lowercase", "number", "symbol", "charset", "symbols", "exclude",
+        "min_upper", "min_lower", "min_digits", "min_symbols", "no_repeat", "words",
+    ])]
+    pub policy: Option<PathBuf>,
+    /// Generate a diceware passphrase of this many words instead
+    #[arg(short, long)]
+    pub words: Option<usize>,
+    /// Separator between passphrase words
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+    /// eff-large or a file with one word per line
+    #[arg(long, value_parser = parse_wordlist, default_value = "eff-large", requires = "words")]
+    pub wordlist: Wordlist,
+    /// Capitalize every passphrase word
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub capitalize: bool,
+    /// Append a random digit to one of the passphrase words
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub add_number: bool,
+    /// Append a random symbol to one of the passphrase words
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub add_symbol: bool,
+    /// Print the entropy of the generated password to stderr
+    #[arg(long, default_value_t = false)]
+    pub entropy: bool,
+    /// Number of passwords to generate
+    #[arg(short, long, default_value_t = 1)]
+    pub count: usize,
+    /// Generate one password per user name in this file, one name per line
+    #[arg(long, value_parser = verify_input_file, conflicts_with = "count")]
+    pub usernames: Option<String>,
+    /// Output format: plain, json or csv
+    #[arg(short, long, value_parser = parse_passwd_format, default_value = "plain")]
+    pub format: PasswdFormat,
+    /// Add a column with the password hashed by argon2id, bcrypt, scrypt or pbkdf2-sha256
+    #[arg(long, value_parser = parse_passwd_hash_algo)]
+    pub hash: Option<PasswdHashAlgo>,
+    /// Seed ChaCha20 for output that stays the same across releases, never use it for real passwords
+    #[arg(long, hide = true)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PasswdFormat {
+    Plain,
+    Json,
+    Csv,
+}
+
+fn parse_passwd_format(format: &str) -> Result<PasswdFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for PasswdFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(PasswdFormat::Plain),
+            "json" => Ok(PasswdFormat::Json),
+            "csv" => Ok(PasswdFormat::Csv),
+            _ => Err(anyhow::anyhow!("Invalid output format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PasswdHashAlgo {
+    Argon2id,
+    Bcrypt,
+    Scrypt,
+    Pbkdf2Sha256,
+}
+
+fn parse_passwd_hash_algo(algo: &str) -> Result<PasswdHashAlgo, anyhow::Error> {
+    algo.parse()
+}
+
+impl FromStr for PasswdHashAlgo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "argon2" | "argon2id" => Ok(PasswdHashAlgo::Argon2id),
+            "bcrypt" => Ok(PasswdHashAlgo::Bcrypt),
+            "scrypt" => Ok(PasswdHashAlgo::Scrypt),
+            "pbkdf2" | "pbkdf2-sha256" => Ok(PasswdHashAlgo::Pbkdf2Sha256),
+            _ => Err(anyhow::anyhow!("Invalid password hash algorithm: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct HashPasswdOpts {
+    /// argon2id, bcrypt, scrypt or pbkdf2-sha256
+    #[arg(short, long, value_parser = parse_passwd_hash_algo, default_value = "argon2id")]
+    pub algo: PasswdHashAlgo,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct VerifyPasswdOpts {
+    /// PHC string such as `$argon2id$...`, or a bcrypt/sha-crypt hash
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct CheckOpts {
+    /// Words an attacker would try first, such as the user or site name
+    #[arg(short, long = "user-input")]
+    pub user_inputs: Vec<String>,
+    /// Fail unless the password scores at least this much (0-4)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Wordlist {
+    EffLarge,
+    File(PathBuf),
+}
+
+fn parse_wordlist(wordlist: &str) -> Result<Wordlist, anyhow::Error> {
+    wordlist.parse()
+}
+
+impl FromStr for Wordlist {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eff-large" => Ok(Wordlist::EffLarge),
+            path if PathBuf::from(path).is_file() => Ok(Wordlist::File(path.into())),
+            _ => Err(anyhow::anyhow!("Wordlist file {} does not exist", s)),
+        }
+    }
+}
+
+impl GenPassOpts {
+    fn policy(&self) -> anyhow::Result<PasswdPolicy> {
+        if let Some(path) = &self.policy {
+            return PasswdPolicy::load(path);
+        }
+        Ok(PasswdPolicy {
+            length: self.length,
+            uppercase: self.uppercase,
+            lowercase: self.lowercase,
+            number: self.number,
+            symbol: self.symbol,
+            charset: self.charset.clone(),
+            symbols: self.symbols.clone(),
+            exclude: self.exclude.clone(),
+            min_upper: self.min_upper,
+            min_lower: self.min_lower,
+            min_digits: self.min_digits,
+            min_symbols: self.min_symbols,
+            no_repeat: self.no_repeat,
+        })
+    }
+
+    /// Generate `count` passwords or passphrases along with their entropy.
+    fn generate(&self, count: usize) -> anyhow::Result<(Vec<String>, f64)> {
+        let mut rng: Box<dyn RngCore> = match self.seed {
+            Some(seed) => Box::new(ChaCha20Rng::seed_from_u64(seed)),
+            None => Box::new(rand::thread_rng()),
+        };
+        if let Some(words) = self.words {
+            let wordlist = load_wordlist(&self.wordlist)?;
+            let passphrases = (0..count)
+                .map(|_| {
+                    process_passphrase_with_rng(
+                        &wordlist,
+                        words,
+                        &self.separator,
+                        self.capitalize,
+                        self.add_number,
+                        self.add_symbol,
+                        &mut rng,
+                    )
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let entropy =
+                passphrase_entropy(wordlist.len(), words, self.add_number, self.add_symbol);
+            return Ok((passphrases, entropy));
+        }
+
+        let policy = self.policy()?;
+        let passwords = (0..count)
+            .map(|_| {
+                Ok(String::from_utf8(process_passwd_with_rng(
+                    &policy, &mut rng,
+                )?)?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok((passwords, policy.entropy()?))
+    }
 }
 
 impl CmdExecutor for GenPassOpts {
     async fn execute(&self) -> anyhow::Result<()> {
-        let passwd = process_passwd(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbol,
-        )?;
-        println!("{}", String::from_utf8(passwd)?);
+        let usernames = match &self.usernames {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)?;
+                let usernames = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                Some(usernames)
+            }
+            None => None,
+        };
+        let count = usernames.as_ref().map_or(self.count, Vec::len);
+
+        let (passwords, entropy) = self.generate(count)?;
+        let records = process_passwd_records(passwords, usernames, self.hash)?;
+        print!("{}", format_passwd_records(&records, self.format)?);
+        if self.entropy {
+            eprintln!("Entropy: {:.1} bits", entropy);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for CheckOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let password = read_password("Password: ")?;
+        let user_inputs = self
+            .user_inputs
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let strength = process_passwd_check(&password, &user_inputs);
+        print!("{}", strength);
+        if let Some(min_score) = self.min_score
+            && strength.score < min_score
+        {
+            return Err(anyhow::anyhow!(
+                "Password scores {}, at least {} is required",
+                strength.score,
+                min_score
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for HashPasswdOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let password = read_password("Password: ")?;
+        println!("{}", hash_passwd(&password, self.algo)?);
+        Ok(())
+    }
+}
+
+impl CmdExecutor for VerifyPasswdOpts {
+    async fn execute(&self) -> anyhow::Result<()> {
+        let password = read_password("Password: ")?;
+        if !verify_passwd(&password, &self.hash)? {
+            return Err(anyhow::anyhow!("Password does not match"));
+        }
+        println!("Password matches");
         Ok(())
     }
 }
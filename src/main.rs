@@ -1,9 +1,15 @@
 use clap::Parser;
 use rcli::*;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+    // info by default so `http serve` prints its access log, RUST_LOG overrides it
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
     let opts = Opts::parse();
 
     opts.cmd.execute().await?;
@@ -1,39 +1,449 @@
-use anyhow::Ok;
-use base64::{Engine as _, prelude::*};
+use std::io::{self, Read, Write};
 
-use crate::{Base64Format, input_reader};
+use anyhow::Result;
+use base64::{
+    Engine as _,
+    alphabet::{self, Alphabet},
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    read::DecoderReader,
+    write::EncoderWriter,
+};
+use data_encoding::{BASE32, BASE32_NOPAD, BASE32HEX, BASE32HEX_NOPAD};
 
-pub fn process_encode(input: &str, format: &Base64Format) -> anyhow::Result<String> {
-    let content = input_reader(input)?;
-    let encoded = match format {
-        Base64Format::Standard => BASE64_STANDARD.encode(&content),
-        Base64Format::UrlSafe => BASE64_URL_SAFE_NO_PAD.encode(&content),
+use crate::{Base64Format, ContentType, Encoding};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions {
+    /// Force `=` padding on or off, `None` keeps the encoding's default
+    pub padding: Option<bool>,
+    /// Break the output into lines of this many characters, 0 disables wrapping
+    pub wrap: Option<usize>,
+    /// End wrapped lines with CRLF as MIME requires
+    pub crlf: bool,
+}
+
+impl Encoding {
+    /// Whether the encoding pads by default, `None` if it has no padding at all.
+    fn default_padding(&self) -> Option<bool> {
+        match self {
+            Encoding::Base64(Base64Format::Standard) | Encoding::Base32 | Encoding::Base32Hex => {
+                Some(true)
+            }
+            Encoding::Base64(Base64Format::UrlSafe) => Some(false),
+            Encoding::Base58 | Encoding::Ascii85 | Encoding::Z85 | Encoding::Hex => None,
+        }
+    }
+}
+
+/// Encodes with or without padding and decodes either form.
+fn base64_engine(format: Base64Format, padding: bool) -> GeneralPurpose {
+    let alphabet: &Alphabet = match format {
+        Base64Format::Standard => &alphabet::STANDARD,
+        Base64Format::UrlSafe => &alphabet::URL_SAFE,
     };
+    let config = GeneralPurposeConfig::new()
+        .with_encode_padding(padding)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    GeneralPurpose::new(alphabet, config)
+}
 
-    Ok(encoded)
+fn wrap_lines(encoded: String, width: usize, crlf: bool) -> String {
+    if width == 0 || encoded.len() <= width {
+        return encoded;
+    }
+    let eol = if crlf { "\r\n" } else { "\n" };
+    // every encoding produces ASCII, so splitting on bytes is safe
+    encoded
+        .as_bytes()
+        .chunks(width)
+        .map(|line| String::from_utf8_lossy(line))
+        .collect::<Vec<_>>()
+        .join(eol)
 }
 
-pub fn process_decode(input: &str, format: &Base64Format) -> anyhow::Result<String> {
-    let content = input_reader(input)?;
-    let decoded = match format {
-        Base64Format::Standard => BASE64_STANDARD.decode(&content),
-        Base64Format::UrlSafe => BASE64_URL_SAFE_NO_PAD.decode(&content),
-    }?;
+pub fn process_encode(
+    content: &[u8],
+    encoding: Encoding,
+    options: &EncodeOptions,
+) -> Result<String> {
+    let padding = match (encoding.default_padding(), options.padding) {
+        (Some(default), padding) => padding.unwrap_or(default),
+        (None, None) => false,
+        (None, Some(_)) => {
+            return Err(anyhow::anyhow!("{} has no padding", <&str>::from(encoding)));
+        }
+    };
+    let encoded = match encoding {
+        Encoding::Base64(format) => base64_engine(format, padding).encode(content),
+        Encoding::Base32 if padding => BASE32.encode(content),
+        Encoding::Base32 => BASE32_NOPAD.encode(content),
+        Encoding::Base32Hex if padding => BASE32HEX.encode(content),
+        Encoding::Base32Hex => BASE32HEX_NOPAD.encode(content),
+        Encoding::Base58 => bs58::encode(content).into_string(),
+        Encoding::Ascii85 => ascii85::encode(content),
+        Encoding::Z85 => z85::encode(content),
+        Encoding::Hex => hex::encode(content),
+    };
+
+    Ok(match options.wrap {
+        Some(width) => wrap_lines(encoded, width, options.crlf),
+        None => encoded,
+    })
+}
+
+/// Inserts a line break every `width` bytes written through it.
+struct LineWrapper<W: Write> {
+    inner: W,
+    width: usize,
+    column: usize,
+    eol: &'static [u8],
+}
+
+impl<W: Write> Write for LineWrapper<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.width == 0 {
+            return self.inner.write(buf);
+        }
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.column == self.width {
+                self.inner.write_all(self.eol)?;
+                self.column = 0;
+            }
+            let n = rest.len().min(self.width - self.column);
+            self.inner.write_all(&rest[..n])?;
+            self.column += n;
+            rest = &rest[n..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-    let decoded = String::from_utf8(decoded)?;
+/// Drops ASCII whitespace from the underlying reader.
+struct SkipWhitespace<R: Read>(R);
 
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[len] = buf[i];
+                    len += 1;
+                }
+            }
+            // a chunk of nothing but whitespace is not the end of the input
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+/// Base64 encode everything `reader` yields straight into `writer`, so memory
+/// use stays bounded no matter how large the input is.
+pub fn process_encode_stream(
+    mut reader: impl Read,
+    writer: impl Write,
+    format: Base64Format,
+    options: &EncodeOptions,
+) -> Result<()> {
+    let padding = options
+        .padding
+        .or(Encoding::Base64(format).default_padding())
+        .unwrap_or_default();
+    let engine = base64_engine(format, padding);
+    let wrapper = LineWrapper {
+        inner: writer,
+        width: options.wrap.unwrap_or(0),
+        column: 0,
+        eol: if options.crlf { b"\r\n" } else { b"\n" },
+    };
+    let mut encoder = EncoderWriter::new(wrapper, &engine);
+    io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Streaming counterpart of `process_decode` for base64, whitespace anywhere
+/// in the input is skipped.
+pub fn process_decode_stream(
+    reader: impl Read,
+    mut writer: impl Write,
+    format: Base64Format,
+) -> Result<()> {
+    let engine = base64_engine(format, true);
+    let mut decoder = DecoderReader::new(SkipWhitespace(reader), &engine);
+    io::copy(&mut decoder, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Whitespace is never part of the encoding, e.g. line wrapping or a trailing
+/// newline from `echo`.
+fn strip_whitespace(content: &[u8]) -> Vec<u8> {
+    content
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect()
+}
+
+pub fn process_decode(content: &[u8], encoding: Encoding) -> Result<Vec<u8>> {
+    let content = strip_whitespace(content);
+    let decoded = match encoding {
+        Encoding::Base64(format) => base64_engine(format, true).decode(&content)?,
+        // padding is optional and TOTP secrets are often written in lowercase
+        Encoding::Base32 | Encoding::Base32Hex => {
+            let end = content
+                .iter()
+                .rposition(|b| *b != b'=')
+                .map_or(0, |i| i + 1);
+            let content = content[..end].to_ascii_uppercase();
+            match encoding {
+                Encoding::Base32 => BASE32_NOPAD.decode(&content)?,
+                _ => BASE32HEX_NOPAD.decode(&content)?,
+            }
+        }
+        Encoding::Base58 => bs58::decode(&content).into_vec()?,
+        Encoding::Ascii85 => ascii85::decode(std::str::from_utf8(&content)?)
+            .map_err(|e| anyhow::anyhow!("Invalid ascii85: {}", e))?,
+        Encoding::Z85 => z85::decode(&content)?,
+        Encoding::Hex => hex::decode(&content)?,
+    };
+
+    Ok(decoded)
+}
+
+/// Build a `data:` URI carrying `content` as base64.
+pub fn process_data_uri(content: &[u8], content_type: ContentType) -> String {
+    format!(
+        "data:{};base64,{}",
+        content_type.mime,
+        base64_engine(Base64Format::Standard, true).encode(content)
+    )
+}
+
+fn percent_decode(s: &str) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next().unwrap_or(0), bytes.next().unwrap_or(0)];
+            let byte = hex::decode(hex)
+                .map_err(|_| anyhow::anyhow!("Invalid percent encoding in data URI"))?;
+            decoded.extend(byte);
+        } else {
+            decoded.push(b);
+        }
+    }
     Ok(decoded)
 }
 
+/// Split a `data:` URI into its MIME type and payload. Payloads without
+/// `;base64` are percent-encoded as RFC 2397 describes.
+pub fn parse_data_uri(content: &[u8]) -> Result<(String, Vec<u8>)> {
+    let uri = std::str::from_utf8(content)?.trim();
+    let rest = uri
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+        .ok_or_else(|| anyhow::anyhow!("Not a data URI"))?;
+    let (meta, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("Invalid data URI: missing ','"))?;
+    let (mime, is_base64) = match meta.strip_suffix(";base64") {
+        Some(mime) => (mime, true),
+        None => (meta, false),
+    };
+    let mime = if mime.is_empty() {
+        "text/plain;charset=US-ASCII"
+    } else {
+        mime
+    };
+    let data = if is_base64 {
+        process_decode(payload.as_bytes(), Encoding::Base64(Base64Format::Standard))?
+    } else {
+        percent_decode(payload)?
+    };
+    Ok((mime.to_string(), data))
+}
+
+/// Encodings `--detect` tries, most restrictive alphabet first so that e.g. a
+/// hex string is not reported as base64.
+const DETECT_CANDIDATES: [Encoding; 7] = [
+    Encoding::Hex,
+    Encoding::Base32,
+    Encoding::Base64(Base64Format::Standard),
+    Encoding::Base64(Base64Format::UrlSafe),
+    Encoding::Base58,
+    Encoding::Ascii85,
+    Encoding::Z85,
+];
+
+fn is_candidate(encoding: Encoding, content: &[u8]) -> bool {
+    match encoding {
+        Encoding::Hex => content.len().is_multiple_of(2),
+        // decoding base32 ignores case, mixed case strongly suggests base64
+        Encoding::Base32 => {
+            !(content.iter().any(u8::is_ascii_lowercase)
+                && content.iter().any(u8::is_ascii_uppercase))
+        }
+        Encoding::Ascii85 => content.starts_with(b"<~"),
+        _ => true,
+    }
+}
+
+/// Try the candidate encodings in turn and return the first one that decodes
+/// the content along with the decoded bytes.
+pub fn process_detect(content: &[u8]) -> Result<(Encoding, Vec<u8>)> {
+    let content = strip_whitespace(content);
+    if content.is_empty() {
+        return Err(anyhow::anyhow!("Nothing to decode"));
+    }
+    DETECT_CANDIDATES
+        .into_iter()
+        .filter(|encoding| is_candidate(*encoding, &content))
+        .find_map(|encoding| {
+            process_decode(&content, encoding)
+                .ok()
+                .map(|decoded| (encoding, decoded))
+        })
+        .ok_or_else(|| anyhow::anyhow!("Input does not match any known encoding"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const ALL: [Encoding; 8] = [
+        Encoding::Base64(Base64Format::Standard),
+        Encoding::Base64(Base64Format::UrlSafe),
+        Encoding::Base32,
+        Encoding::Base32Hex,
+        Encoding::Base58,
+        Encoding::Ascii85,
+        Encoding::Z85,
+        Encoding::Hex,
+    ];
+
+    #[test]
+    fn test_base64_codec() -> Result<()> {
+        let standard = Encoding::Base64(Base64Format::Standard);
+        let encoded = process_encode(b"Hello, World!", standard, &EncodeOptions::default())?;
+        let decoded = process_decode(encoded.as_bytes(), standard)?;
+        assert_eq!(b"Hello, World!", decoded.as_slice());
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_binary_roundtrip() -> Result<()> {
+        let content = (0..=255u8).collect::<Vec<_>>();
+        for encoding in ALL {
+            let encoded = process_encode(&content, encoding, &EncodeOptions::default())?;
+            let decoded = process_decode(format!("{}\n", encoded).as_bytes(), encoding)?;
+            assert_eq!(content, decoded, "{:?}", encoding);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encodings() -> Result<()> {
+        let options = EncodeOptions::default();
+        let no_pad = EncodeOptions {
+            padding: Some(false),
+            ..options
+        };
+        assert_eq!(
+            process_encode(b"foobar", Encoding::Base32, &options)?,
+            "MZXW6YTBOI======"
+        );
+        assert_eq!(
+            process_encode(b"foobar", Encoding::Base32, &no_pad)?,
+            "MZXW6YTBOI"
+        );
+        assert_eq!(process_decode(b"mzxw6ytboi", Encoding::Base32)?, b"foobar");
+        assert_eq!(
+            process_encode(b"hello world", Encoding::Base58, &options)?,
+            "StV1DL6CwTryKyV"
+        );
+        assert_eq!(
+            process_encode(
+                &[0x86, 0x4f, 0xd2, 0x6f, 0xb5, 0x59, 0xf7, 0x5b],
+                Encoding::Z85,
+                &options
+            )?,
+            "HelloWorld"
+        );
+        assert!(process_encode(b"foobar", Encoding::Hex, &no_pad).is_err());
+
+        let mime = EncodeOptions {
+            padding: Some(true),
+            wrap: Some(4),
+            crlf: true,
+        };
+        let encoded = process_encode(b"foobar", Encoding::Base64(Base64Format::UrlSafe), &mime)?;
+        assert_eq!(encoded, "Zm9v\r\nYmFy");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_detect() -> Result<()> {
+        let cases: [(&[u8], Encoding); 5] = [
+            (b"6865\n6c6c6f\n", Encoding::Hex),
+            (b"NBSWY3DP", Encoding::Base32),
+            (b"aGVs\nbG8h", Encoding::Base64(Base64Format::Standard)),
+            (b"-_-_", Encoding::Base64(Base64Format::UrlSafe)),
+            (b"<~BOu!rDZ~>", Encoding::Ascii85),
+        ];
+        for (content, expected) in cases {
+            let (encoding, decoded) = process_detect(content)?;
+            assert_eq!(encoding, expected);
+            assert_eq!(decoded, process_decode(content, expected)?);
+        }
+        assert!(process_detect("not, base64 ~".as_bytes()).is_err());
+        Ok(())
+    }
+
     #[test]
-    fn test_base64_codec() -> anyhow::Result<()> {
-        let encoded = process_encode("Hello, World!", &Base64Format::Standard)?;
-        let decoded = process_decode(&encoded, &Base64Format::Standard)?;
-        assert_eq!("Hello, World!", decoded);
+    fn test_stream_matches_buffered() -> Result<()> {
+        let content = (0..5000u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        let options = EncodeOptions {
+            padding: None,
+            wrap: Some(76),
+            crlf: false,
+        };
+        let mut encoded = Vec::new();
+        process_encode_stream(&content[..], &mut encoded, Base64Format::Standard, &options)?;
+        let expected =
+            process_encode(&content, Encoding::Base64(Base64Format::Standard), &options)?;
+        assert_eq!(String::from_utf8(encoded.clone())?, expected);
+
+        let mut decoded = Vec::new();
+        process_decode_stream(&encoded[..], &mut decoded, Base64Format::Standard)?;
+        assert_eq!(decoded, content);
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_uri() -> Result<()> {
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let uri = process_data_uri(&png, crate::sniff_content_type(&png));
+        assert_eq!(uri, "data:image/png;base64,iVBORw0KGgo=");
+        assert_eq!(parse_data_uri(uri.as_bytes())?, ("image/png".into(), png));
+
+        let (mime, text) = parse_data_uri(b"data:,Hello%2C%20World%21\n")?;
+        assert_eq!(mime, "text/plain;charset=US-ASCII");
+        assert_eq!(text, b"Hello, World!");
+        assert!(parse_data_uri(b"aGVsbG8h").is_err());
         Ok(())
     }
 }
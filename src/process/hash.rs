@@ -0,0 +1,258 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::Result;
+use md5::Md5;
+use rayon::prelude::*;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::HashAlgo;
+
+const BUF_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    Unreadable,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Failed => "FAILED",
+            CheckStatus::Unreadable => "FAILED open or read",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+fn read_chunks(mut rdr: impl Read, mut f: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buf = vec![0u8; BUF_SIZE];
+    loop {
+        let n = rdr.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        f(&buf[..n]);
+    }
+}
+
+fn digest_reader<D: Digest>(rdr: impl Read) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    read_chunks(rdr, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Hash everything `rdr` yields and return the lowercase hex digest.
+pub fn hash_reader(algo: HashAlgo, rdr: impl Read) -> Result<String> {
+    let digest = match algo {
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(rdr)?;
+            hasher.finalize().as_bytes().to_vec()
+        }
+        HashAlgo::Sha256 => digest_reader::<Sha256>(rdr)?,
+        HashAlgo::Sha512 => digest_reader::<Sha512>(rdr)?,
+        HashAlgo::Sha1 => digest_reader::<Sha1>(rdr)?,
+        HashAlgo::Md5 => digest_reader::<Md5>(rdr)?,
+        HashAlgo::Xxh3 => {
+            let mut hasher = Xxh3::new();
+            read_chunks(rdr, |chunk| hasher.update(chunk))?;
+            hasher.digest().to_be_bytes().to_vec()
+        }
+    };
+    Ok(hex::encode(digest))
+}
+
+pub fn hash_file(algo: HashAlgo, path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    match algo {
+        // memory-map large files and hash them on all cores
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_mmap_rayon(path)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+        _ => hash_reader(algo, File::open(path)?),
+    }
+}
+
+/// Walk `path` in order. Symlinked directories below the inputs are skipped,
+/// following them could loop forever.
+fn collect_files(path: &Path, files: &mut Vec<String>) -> Result<()> {
+    if path.is_dir() {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_symlink() && path.is_dir() {
+                continue;
+            }
+            entries.push(path);
+        }
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files)?;
+        }
+    } else {
+        files.push(path.display().to_string());
+    }
+    Ok(())
+}
+
+/// Hash the given files, `-` being stdin. Directories are walked recursively
+/// and everything is hashed in parallel; results keep the input order.
+pub fn process_hash(inputs: &[String], algo: HashAlgo) -> Result<Vec<(String, Result<String>)>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        collect_files(Path::new(input), &mut files)?;
+    }
+
+    let digests = files
+        .into_par_iter()
+        .map(|file| {
+            let digest = if file == "-" {
+                hash_reader(algo, io::stdin().lock())
+            } else {
+                hash_file(algo, &file)
+            };
+            (file, digest)
+        })
+        .collect();
+    Ok(digests)
+}
+
+/// Parse a `<digest>  <path>` line as written by `sha256sum` and friends;
+/// a `*` in front of the path marks binary mode and is ignored. Without an
+/// `algo` the candidates come from the digest length.
+fn parse_checksum_line(line: &str, algo: Option<HashAlgo>) -> Option<(Vec<HashAlgo>, &str, &str)> {
+    let (digest, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*']).unwrap_or(path);
+    if path.is_empty() || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let algos = match algo {
+        Some(algo) if algo.hex_len() == digest.len() => vec![algo],
+        Some(_) => return None,
+        None => HashAlgo::from_hex_len(digest.len()).to_vec(),
+    };
+    (!algos.is_empty()).then_some((algos, digest, path))
+}
+
+pub fn process_check(
+    checksums: &str,
+    algo: Option<HashAlgo>,
+) -> Result<Vec<(String, CheckStatus)>> {
+    let content = if checksums == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(checksums)?
+    };
+    let entries = content
+        .lines()
+        .filter_map(|line| parse_checksum_line(line, algo))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No properly formatted checksum lines found in {}",
+            checksums
+        ));
+    }
+
+    let results = entries
+        .into_par_iter()
+        .map(|(algos, expected, path)| {
+            let mut status = CheckStatus::Failed;
+            for algo in algos {
+                match hash_file(algo, path) {
+                    Ok(digest) if digest.eq_ignore_ascii_case(expected) => {
+                        status = CheckStatus::Ok;
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(_) => {
+                        status = CheckStatus::Unreadable;
+                        break;
+                    }
+                }
+            }
+            (path.to_string(), status)
+        })
+        .collect();
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_reader() -> Result<()> {
+        let cases = [
+            (
+                HashAlgo::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (HashAlgo::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (HashAlgo::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                HashAlgo::Blake3,
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+            (HashAlgo::Xxh3, "78af5f94892f3950"),
+        ];
+        for (algo, expected) in cases {
+            assert_eq!(hash_reader(algo, "abc".as_bytes())?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_check() -> Result<()> {
+        let digest = hash_file(HashAlgo::Sha256, "Cargo.toml")?;
+        let path = std::env::temp_dir().join(format!("rcli-SHA256SUMS-{}", std::process::id()));
+        fs::write(
+            &path,
+            format!("{digest}  Cargo.toml\n{digest} *fixtures/blake3.txt\n{digest}  missing\n"),
+        )?;
+
+        // the length of a SHA-256 digest, but not of an MD5 one
+        let results = process_check(path.to_str().unwrap(), None)?;
+        assert!(process_check(path.to_str().unwrap(), Some(HashAlgo::Md5)).is_err());
+        fs::remove_file(&path)?;
+        assert_eq!(
+            results,
+            vec![
+                ("Cargo.toml".to_string(), CheckStatus::Ok),
+                ("fixtures/blake3.txt".to_string(), CheckStatus::Failed),
+                ("missing".to_string(), CheckStatus::Unreadable),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_check_default_output() -> Result<()> {
+        // what `rcli hash Cargo.toml fixtures/blake3.txt > SUMS` writes
+        let inputs = ["Cargo.toml".to_string(), "fixtures/blake3.txt".to_string()];
+        let sums = process_hash(&inputs, HashAlgo::Blake3)?
+            .into_iter()
+            .map(|(path, digest)| Ok(format!("{}  {}\n", digest?, path)))
+            .collect::<Result<String>>()?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("SUMS");
+        fs::write(&path, sums)?;
+
+        let results = process_check(path.to_str().unwrap(), None)?;
+        assert!(results.iter().all(|(_, status)| *status == CheckStatus::Ok));
+        Ok(())
+    }
+}
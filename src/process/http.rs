@@ -1,61 +1,248 @@
 use anyhow::Result;
 use axum::{
-    Router,
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    Json, Router,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{Html, IntoResponse, Response},
     routing::get,
 };
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
-use tower_http::services::ServeDir;
+use axum_server::tls_rustls::RustlsConfig;
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Component, PathBuf},
+    sync::Arc,
+};
 use tracing::info;
 
+use super::http_layers::with_layers;
+use crate::{
+    HttpAuth, ListingQuery, TlsConfig, TlsIdentity, put_handler, read_dir_listing,
+    render_listing_html, root_upload_handler, serve_file, upload_handler,
+};
+
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Serve symlinks that point outside the served directory
+    pub follow_symlinks: bool,
+    /// Serve and list dotfiles such as `.git` or `.env`
+    pub show_hidden: bool,
+    /// Accept multipart `POST` and raw `PUT` uploads
+    pub upload: bool,
+    /// Largest upload accepted, in bytes
+    pub max_upload_size: u64,
+    /// Let uploads replace existing files
+    pub overwrite: bool,
+    /// Credentials required for every request, none by default
+    pub auth: HttpAuth,
+    /// Origins allowed to make cross-origin requests, `*` for any
+    pub cors: Vec<String>,
+    /// Compress responses when the client accepts it
+    pub compress: bool,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: false,
+            show_hidden: false,
+            upload: false,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+            overwrite: false,
+            auth: HttpAuth::default(),
+            cors: Vec::new(),
+            compress: true,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AppState {
+    /// Canonical path of the served directory
     path: PathBuf,
+    pub(crate) options: HttpOptions,
+}
+
+impl AppState {
+    pub fn new(path: impl AsRef<std::path::Path>, options: HttpOptions) -> Result<Self> {
+        let path = std::fs::canonicalize(path)?;
+        Ok(Self { path, options })
+    }
+
+    /// Map a decoded request path to a file below the served directory.
+    /// `..`, absolute paths and drive prefixes are refused before touching
+    /// the file system, symlinks are checked once resolved.
+    pub(crate) async fn resolve(&self, path: &str) -> Result<PathBuf, StatusCode> {
+        let mut file = self.path.clone();
+        for segment in path.split(['/', '\\']) {
+            let mut components = std::path::Path::new(segment).components();
+            match (components.next(), components.next()) {
+                (None | Some(Component::CurDir), None) => {}
+                (Some(Component::Normal(name)), None) => file.push(name),
+                _ => return Err(StatusCode::FORBIDDEN),
+            }
+        }
+        self.check(&file).await
+    }
+
+    /// Canonicalize `file` and apply the symlink and dotfile policies.
+    async fn check(&self, file: &std::path::Path) -> Result<PathBuf, StatusCode> {
+        let canonical = tokio::fs::canonicalize(file)
+            .await
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let relative = match canonical.strip_prefix(&self.path) {
+            Ok(relative) => relative,
+            Err(_) if self.options.follow_symlinks => return Ok(canonical),
+            Err(_) => return Err(StatusCode::FORBIDDEN),
+        };
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        if hidden && !self.options.show_hidden {
+            // as if it wasn't there, rather than confirming it exists
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(canonical)
+    }
+}
+
+/// Whether the client asked for JSON rather than a page for browsers.
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
+/// Responses for the statuses `AppState::resolve` fails with.
+pub(crate) fn error_response(status: StatusCode) -> (StatusCode, Response) {
+    let message = match status {
+        StatusCode::NOT_FOUND => "File not found",
+        status => status.canonical_reason().unwrap_or("Error"),
+    };
+    (status, message.into_response())
+}
+
+fn internal_error(e: impl std::fmt::Display) -> (StatusCode, Response) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Internal server error: {}", e).into_response(),
+    )
+}
+
+async fn root_handler(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    Query(query): Query<ListingQuery>,
+    headers: HeaderMap,
+) -> (StatusCode, Response) {
+    serve_path(&state, "", &method, query, &headers).await
 }
 
 async fn index_handler(
     State(state): State<Arc<AppState>>,
     Path(path): Path<String>,
+    method: Method,
+    Query(query): Query<ListingQuery>,
+    headers: HeaderMap,
 ) -> (StatusCode, Response) {
-    let path = std::path::Path::new(&state.path).join(path);
-    info!("Reading file {:?}", path);
-    if !path.exists() {
-        (StatusCode::NOT_FOUND, "File not found".into_response())
-    } else {
-        let content = tokio::fs::read_to_string(path).await;
-        match content {
-            Ok(content) => (StatusCode::OK, content.into_response()),
-            Err(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Internal server error: {}", e).into_response(),
-            ),
+    serve_path(&state, &path, &method, query, &headers).await
+}
+
+async fn serve_path(
+    state: &AppState,
+    path: &str,
+    method: &Method,
+    query: ListingQuery,
+    headers: &HeaderMap,
+) -> (StatusCode, Response) {
+    let file = match state.resolve(path).await {
+        Ok(file) => file,
+        Err(status) => return error_response(status),
+    };
+    info!("Reading file {:?}", file);
+    if file.is_dir() {
+        let mut listing = match read_dir_listing(&file, path, query).await {
+            Ok(listing) => listing,
+            Err(e) => return internal_error(e),
+        };
+        // only list what could be served
+        let mut entries = Vec::with_capacity(listing.entries.len());
+        for entry in listing.entries {
+            if state.check(&file.join(&entry.name)).await.is_ok() {
+                entries.push(entry);
+            }
         }
+        listing.entries = entries;
+        if wants_json(headers) {
+            return (StatusCode::OK, Json(listing).into_response());
+        }
+        let html = render_listing_html(&listing, query, state.options.upload);
+        return (StatusCode::OK, Html(html).into_response());
+    }
+    match serve_file(&file, method, headers).await {
+        Ok(response) => response,
+        Err(e) => internal_error(e),
     }
 }
 
-pub async fn process_http(path: PathBuf, port: u16) -> Result<()> {
-    info!(
-        "Processing HTTP request for path: {} on port: {}",
-        path.display(),
-        port
-    );
-    let app_state = AppState { path: path.clone() };
-    let dir_service = ServeDir::new(path)
-        .append_index_html_on_directories(true)
-        .precompressed_gzip()
-        .precompressed_br()
-        .precompressed_deflate()
-        .precompressed_zstd();
-
-    let router = Router::new()
-        .route("/{*lang}", get(index_handler))
-        .nest_service("/tower", dir_service)
-        .with_state(Arc::new(app_state));
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, router.into_make_service()).await?;
+/// The file server routes, `path` is the directory to serve.
+pub fn http_router(path: impl AsRef<std::path::Path>, options: HttpOptions) -> Result<Router> {
+    let state = AppState::new(path, options)?;
+    let router = if state.options.upload {
+        // leave room for the multipart boundaries and headers around the files
+        let limit = state.options.max_upload_size.saturating_add(64 * 1024);
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        Router::new()
+            .route("/", get(root_handler).post(root_upload_handler))
+            .route(
+                "/{*path}",
+                get(index_handler).post(upload_handler).put(put_handler),
+            )
+            .layer(DefaultBodyLimit::max(limit))
+    } else {
+        Router::new()
+            .route("/", get(root_handler))
+            .route("/{*path}", get(index_handler))
+    };
+    let options = state.options.clone();
+    with_layers(router.with_state(Arc::new(state)), &options)
+}
+
+pub async fn process_http(
+    path: PathBuf,
+    addr: SocketAddr,
+    options: HttpOptions,
+    tls: Option<TlsConfig>,
+) -> Result<()> {
+    info!("Serving {} on {}", path.display(), addr);
+    let router = http_router(path, options)?;
+    // a wildcard address can't be browsed to, point at the local machine
+    let host = match addr.ip() {
+        ip if ip.is_unspecified() => "localhost".to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+        ip => ip.to_string(),
+    };
+    match tls {
+        Some(tls) => {
+            let identity = TlsIdentity::load(&tls)?;
+            if let TlsConfig::SelfSigned(names) = &tls {
+                println!("Self-signed certificate for {}", names.join(", "));
+            }
+            println!("SHA-256 fingerprint: {}", identity.fingerprint());
+            println!("Serving on https://{}:{}", host, addr.port());
+            let config = RustlsConfig::from_config(identity.server_config()?);
+            axum_server::bind_rustls(addr, config)
+                .serve(router.into_make_service())
+                .await?;
+        }
+        None => {
+            println!("Serving on http://{}:{}", host, addr.port());
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, router.into_make_service()).await?;
+        }
+    }
     anyhow::Ok(())
 }
 
@@ -65,16 +252,88 @@ mod tests {
 
     use super::*;
 
+    async fn request(method: Method, path: &str, headers: HeaderMap) -> (StatusCode, Response) {
+        let state = Arc::new(AppState::new(".", HttpOptions::default()).unwrap());
+        index_handler(
+            State(state),
+            Path(path.into()),
+            method,
+            Query(ListingQuery::default()),
+            headers,
+        )
+        .await
+    }
+
+    async fn get(path: &str, headers: HeaderMap) -> (StatusCode, String) {
+        let (status, content) = request(Method::GET, path, headers).await;
+        let body = to_bytes(content.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
     #[tokio::test]
     async fn test_index_handler() {
-        let state = Arc::new(AppState {
-            path: PathBuf::from("."),
-        });
+        let (status, body) = get("Cargo.toml", HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("[package]"));
+    }
 
-        let (status, content) = index_handler(State(state), Path("Cargo.toml".into())).await;
-        let body = to_bytes(content.into_body(), usize::MAX).await.unwrap();
-        let body_str = String::from_utf8_lossy(&body);
+    #[tokio::test]
+    async fn test_index_handler_lists_directories() {
+        let (status, body) = get("fixtures", HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"<a href="/fixtures/policy.yaml">policy.yaml</a>"#));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        let (status, body) = get("fixtures/", headers).await;
+        assert_eq!(status, StatusCode::OK);
+        let listing: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(listing["path"], "/fixtures/");
+        let entries = listing["entries"].as_array().unwrap();
+        assert!(
+            entries
+                .iter()
+                .any(|e| e["name"] == "policy.yaml" && e["type"] == "file")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_handler_serves_binary_files() {
+        let (status, response) =
+            request(Method::GET, "fixtures/ed25519.sk", HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/octet-stream"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, std::fs::read("fixtures/ed25519.sk").unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=4-7".parse().unwrap());
+        let (status, response) = request(Method::GET, "fixtures/ed25519.sk", headers).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 4-7/32");
+        let range = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(range, body[4..8]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let (status, _) = request(Method::GET, "fixtures/ed25519.sk", headers).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let (status, response) = request(Method::HEAD, "Cargo.toml", HeaderMap::new()).await;
         assert_eq!(status, StatusCode::OK);
-        assert!(body_str.starts_with("[package]"));
+        assert_eq!(
+            response.headers()[header::CONTENT_LENGTH],
+            std::fs::metadata("Cargo.toml").unwrap().len().to_string()
+        );
+        assert!(
+            to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+                .is_empty()
+        );
     }
 }
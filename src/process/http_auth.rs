@@ -0,0 +1,341 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+use crate::{
+    Decryptor, Ed25519, Ed25519Verifier, Encryptor, Input, SignatureFormat, percent_encode,
+    read_key, secure_eq, verify_passwd,
+};
+
+#[derive(Clone)]
+enum Credential {
+    Password(String),
+    /// bcrypt or any other hash `verify_passwd` understands
+    Hash(String),
+}
+
+/// Who may access the server. Nothing is required when no user, token or
+/// URL key is configured.
+#[derive(Clone, Default)]
+pub struct HttpAuth {
+    users: Vec<(String, Credential)>,
+    tokens: Vec<String>,
+    url_key: Option<Arc<Ed25519Verifier>>,
+    /// `user:password` pairs already checked against a hash, bcrypt is far
+    /// too slow to run on every request a browser makes
+    verified: Arc<Mutex<HashSet<blake3::Hash>>>,
+}
+
+// keep credentials out of logs
+impl fmt::Debug for HttpAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpAuth")
+            .field("users", &self.users.len())
+            .field("tokens", &self.tokens.len())
+            .field("url_key", &self.url_key.is_some())
+            .finish()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// What gets signed for a URL, `path` as it appears in the request line.
+fn signed_message(path: &str, expires: u64) -> String {
+    format!("{}\n{}", path, expires)
+}
+
+/// A link to `path` that works without credentials until `expires` (unix
+/// seconds). Each segment of `path` is percent-encoded.
+pub fn sign_url(key: &Ed25519, path: &str, expires: u64) -> Result<String> {
+    let path = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .fold(String::new(), |url, segment| {
+            url + "/" + &percent_encode(segment)
+        });
+    let path = if path.is_empty() { "/".into() } else { path };
+    let sig = key.encrypt(signed_message(&path, expires).as_bytes())?;
+    Ok(format!(
+        "{}?expires={}&sig={}",
+        path,
+        expires,
+        SignatureFormat::Base64Url.encode(&sig)
+    ))
+}
+
+/// Sign a link to `path` valid for `ttl`, prefixed with `base_url` if given.
+pub fn process_http_sign(
+    path: &str,
+    key: &Input,
+    passphrase: Option<&str>,
+    ttl: Duration,
+    base_url: Option<&str>,
+) -> Result<String> {
+    let key = Ed25519::load_key(read_key(key, passphrase)?)?;
+    let url = sign_url(&key, path, now() + ttl.as_secs())?;
+    Ok(format!(
+        "{}{}",
+        base_url.unwrap_or_default().trim_end_matches('/'),
+        url
+    ))
+}
+
+impl HttpAuth {
+    pub fn user(mut self, name: &str, password: &str) -> Self {
+        let credential = Credential::Password(password.into());
+        self.users.push((name.into(), credential));
+        self
+    }
+
+    /// Add the users of an Apache style `user:hash` file, e.g. from
+    /// `htpasswd -B`.
+    pub fn htpasswd(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, hash) = line.split_once(':').ok_or_else(|| {
+                anyhow::anyhow!("{}:{}: expected user:hash", path.display(), i + 1)
+            })?;
+            self.users
+                .push((name.into(), Credential::Hash(hash.into())));
+        }
+        Ok(self)
+    }
+
+    pub fn token(mut self, token: &str) -> Self {
+        self.tokens.push(token.into());
+        self
+    }
+
+    /// Accept links signed by the secret key belonging to `key`.
+    pub fn url_key(mut self, key: Ed25519Verifier) -> Self {
+        self.url_key = Some(Arc::new(key));
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.users.is_empty() || !self.tokens.is_empty() || self.url_key.is_some()
+    }
+
+    /// Answer a basic auth login from plain passwords and earlier hash
+    /// checks. `None` if only verifying a password hash can tell.
+    fn check_basic(&self, name: &str, password: &str) -> Option<bool> {
+        let key = blake3::hash(format!("{}:{}", name, password).as_bytes());
+        if self.verified().contains(&key) {
+            return Some(true);
+        }
+        let mut needs_hash = false;
+        for (user, credential) in &self.users {
+            match credential {
+                _ if !secure_eq(user, name) => {}
+                Credential::Password(expected) if secure_eq(expected, password) => {
+                    return Some(true);
+                }
+                Credential::Password(_) => {}
+                Credential::Hash(_) => needs_hash = true,
+            }
+        }
+        (!needs_hash).then_some(false)
+    }
+
+    /// Verify `password` against the hashes of `name`, slow by design. The
+    /// cache is only locked to remember a match.
+    fn check_hashes(&self, name: &str, password: &str) -> bool {
+        let matched = self
+            .users
+            .iter()
+            .any(|(user, credential)| match credential {
+                Credential::Hash(hash) if secure_eq(user, name) => {
+                    verify_passwd(password, hash).unwrap_or(false)
+                }
+                _ => false,
+            });
+        if matched {
+            let key = blake3::hash(format!("{}:{}", name, password).as_bytes());
+            self.verified().insert(key);
+        }
+        matched
+    }
+
+    fn verified(&self) -> MutexGuard<'_, HashSet<blake3::Hash>> {
+        self.verified.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn check_bearer(&self, token: &str) -> bool {
+        self.tokens.iter().any(|t| secure_eq(t, token.trim()))
+    }
+
+    /// Whether the `expires` and `sig` query parameters make a valid link.
+    /// `None` if the URL isn't signed at all.
+    pub fn check_signed_url(&self, path: &str, query: Option<&str>) -> Option<bool> {
+        let (mut expires, mut sig) = (None, None);
+        for pair in query.unwrap_or_default().split('&') {
+            match pair.split_once('=') {
+                Some(("expires", value)) => expires = Some(value),
+                Some(("sig", value)) => sig = Some(value),
+                _ => {}
+            }
+        }
+        let (expires, sig) = (expires?, sig?);
+        let valid = (|| {
+            let key = self.url_key.as_ref()?;
+            let expires = expires.parse::<u64>().ok().filter(|e| *e > now())?;
+            let sig = SignatureFormat::Base64Url.decode(sig).ok()?;
+            key.decrypt(signed_message(path, expires).as_bytes(), &sig)
+                .ok()
+        })();
+        Some(valid.unwrap_or(false))
+    }
+
+    /// Check the `Authorization` header, only going to the blocking pool
+    /// when a password hash has to be verified.
+    async fn check_headers(self: &Arc<Self>, headers: &HeaderMap) -> bool {
+        let Some((scheme, credentials)) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+        else {
+            return false;
+        };
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return self.check_bearer(credentials);
+        }
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return false;
+        }
+        let Some((name, password)) = BASE64_STANDARD
+            .decode(credentials.trim())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|decoded| {
+                let (name, password) = decoded.split_once(':')?;
+                Some((name.to_string(), password.to_string()))
+            })
+        else {
+            return false;
+        };
+        if let Some(authorized) = self.check_basic(&name, &password) {
+            return authorized;
+        }
+        let auth = self.clone();
+        tokio::task::spawn_blocking(move || auth.check_hashes(&name, &password))
+            .await
+            .unwrap_or(false)
+    }
+
+    fn unauthorized(&self, status: StatusCode) -> Response {
+        let mut response = (status, status.canonical_reason().unwrap_or_default()).into_response();
+        let headers = response.headers_mut();
+        if !self.users.is_empty() {
+            headers.append(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="rcli", charset="UTF-8""#),
+            );
+        }
+        if !self.tokens.is_empty() {
+            headers.append(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Bearer realm="rcli""#),
+            );
+        }
+        response
+    }
+}
+
+/// Middleware letting requests through with valid basic auth, a bearer
+/// token, or a signed link. Signed links are read only.
+pub async fn http_auth(
+    State(auth): State<Arc<HttpAuth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let read_only = request.method() == Method::GET || request.method() == Method::HEAD;
+    let signed = match read_only {
+        true => auth.check_signed_url(request.uri().path(), request.uri().query()),
+        false => None,
+    };
+    if signed == Some(true) {
+        return next.run(request).await;
+    }
+
+    if auth.check_headers(request.headers()).await {
+        return next.run(request).await;
+    }
+    match signed {
+        // a link that expired or was tampered with
+        Some(false) => auth.unauthorized(StatusCode::FORBIDDEN),
+        _ => auth.unauthorized(StatusCode::UNAUTHORIZED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::KeyLoader;
+
+    use super::*;
+
+    #[test]
+    fn test_signed_url() -> Result<()> {
+        let key = Ed25519::load("fixtures/ed25519.sk")?;
+        let auth = HttpAuth::default().url_key(Ed25519Verifier::load("fixtures/ed25519.pk")?);
+
+        let expires = now() + 60;
+        let url = sign_url(&key, "docs/my notes.txt", expires)?;
+        let (path, query) = url.split_once('?').unwrap();
+        assert_eq!(path, "/docs/my%20notes.txt");
+        assert_eq!(auth.check_signed_url(path, Some(query)), Some(true));
+        assert_eq!(
+            auth.check_signed_url("/docs/other.txt", Some(query)),
+            Some(false)
+        );
+        let tampered = query.replace(&expires.to_string(), &(expires + 1).to_string());
+        assert_eq!(auth.check_signed_url(path, Some(&tampered)), Some(false));
+        assert_eq!(auth.check_signed_url(path, Some("sort=name")), None);
+
+        let url = sign_url(&key, "/docs/a.txt", now() - 1)?;
+        let (path, query) = url.split_once('?').unwrap();
+        assert_eq!(auth.check_signed_url(path, Some(query)), Some(false));
+        Ok(())
+    }
+
+    #[test]
+    fn test_basic_auth_caches_hash_checks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let htpasswd = dir.path().join("htpasswd");
+        std::fs::write(&htpasswd, format!("bob:{}\n", bcrypt::hash("hunter2", 4)?))?;
+        let auth = HttpAuth::default()
+            .user("alice", "wonderland")
+            .htpasswd(&htpasswd)?;
+
+        assert_eq!(auth.check_basic("alice", "wonderland"), Some(true));
+        assert_eq!(auth.check_basic("alice", "hunter2"), Some(false));
+        // only a hash can tell, until it matched once
+        assert_eq!(auth.check_basic("bob", "hunter2"), None);
+        assert!(!auth.check_hashes("bob", "wonderland"));
+        assert!(auth.check_hashes("bob", "hunter2"));
+        assert_eq!(auth.check_basic("bob", "hunter2"), Some(true));
+        assert_eq!(auth.check_basic("bob", "wonderland"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use std::{io::SeekFrom, path::Path, time::SystemTime};
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use httpdate::HttpDate;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::guess_content_type;
+
+/// Bytes read from the start of a file to sniff its type when the extension
+/// doesn't tell.
+const SNIFF_LEN: u64 = 512;
+
+/// What a `Range` header asks for, given the length of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range, serve the whole file
+    Full,
+    /// First and last byte, both inclusive
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parse a single `bytes=` range. Multiple ranges and syntactically invalid
+/// ones are ignored, which RFC 9110 allows, and the whole file is served.
+pub fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        // suffix range: the last `n` bytes
+        return match end.parse::<u64>() {
+            Ok(n) if n > 0 && len > 0 => ByteRange::Partial(len.saturating_sub(n), len - 1),
+            Ok(_) => ByteRange::Unsatisfiable,
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = match end.parse::<u64>() {
+        _ if end.is_empty() => u64::MAX,
+        Ok(end) if end >= start => end,
+        _ => return ByteRange::Full,
+    };
+    if start < len {
+        ByteRange::Partial(start, end.min(len - 1))
+    } else {
+        ByteRange::Unsatisfiable
+    }
+}
+
+/// Strong validator from the modification time and size, like nginx does.
+fn etag(modified: Option<SystemTime>, len: u64) -> String {
+    let secs = modified
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    format!("\"{:x}-{:x}\"", secs, len)
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// `If-None-Match` wins over `If-Modified-Since` when both are sent.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: Option<HttpDate>) -> bool {
+    if let Some(if_none_match) = header_str(headers, header::IF_NONE_MATCH) {
+        let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        return if_none_match.trim() == "*"
+            || if_none_match.split(',').any(|tag| weak(tag) == weak(etag));
+    }
+    match (
+        header_str(headers, header::IF_MODIFIED_SINCE).and_then(|v| v.parse::<HttpDate>().ok()),
+        modified,
+    ) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// A `Range` only applies when `If-Range` is missing or still matches the file.
+fn range_applies(headers: &HeaderMap, etag: &str, modified: Option<HttpDate>) -> bool {
+    match header_str(headers, header::IF_RANGE) {
+        None => true,
+        Some(if_range) if if_range.trim().starts_with('"') => if_range.trim() == etag,
+        Some(if_range) => if_range
+            .parse::<HttpDate>()
+            .ok()
+            .is_some_and(|date| Some(date) == modified),
+    }
+}
+
+/// Serve the file at `path` with its content type, validators and range
+/// support. The body is streamed, `HEAD` only gets the headers.
+pub async fn serve_file(
+    path: &Path,
+    method: &Method,
+    headers: &HeaderMap,
+) -> std::io::Result<(StatusCode, Response)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let metadata = file.metadata().await?;
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let last_modified = modified.map(HttpDate::from);
+    let etag = etag(modified, len);
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Some(last_modified) = last_modified
+        && let Ok(value) = HeaderValue::from_str(&last_modified.to_string())
+    {
+        response_headers.insert(header::LAST_MODIFIED, value);
+    }
+
+    if is_not_modified(headers, &etag, last_modified) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            (response_headers, Body::empty()).into_response(),
+        ));
+    }
+
+    let mut head = Vec::new();
+    (&mut file).take(SNIFF_LEN).read_to_end(&mut head).await?;
+    let content_type = guess_content_type(Some(path), &head);
+    let content_type = if content_type.mime.starts_with("text/") {
+        format!("{}; charset=utf-8", content_type.mime)
+    } else {
+        content_type.mime.to_string()
+    };
+    if let Ok(value) = HeaderValue::from_str(&content_type) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+
+    let range = match header_str(headers, header::RANGE) {
+        Some(range) if range_applies(headers, &etag, last_modified) => parse_range(range, len),
+        _ => ByteRange::Full,
+    };
+    let (status, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial(start, last) => {
+            let content_range = format!("bytes {}-{}/{}", start, last, len);
+            if let Ok(value) = HeaderValue::from_str(&content_range) {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            (StatusCode::PARTIAL_CONTENT, start, last + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                (response_headers, Body::empty()).into_response(),
+            ));
+        }
+    };
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(end - start));
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        file.seek(SeekFrom::Start(start)).await?;
+        Body::from_stream(ReaderStream::new(file.take(end - start)))
+    };
+    Ok((status, (response_headers, body).into_response()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let cases = [
+            ("bytes=0-99", ByteRange::Partial(0, 99)),
+            ("bytes=100-", ByteRange::Partial(100, 999)),
+            ("bytes=-100", ByteRange::Partial(900, 999)),
+            ("bytes=-5000", ByteRange::Partial(0, 999)),
+            ("bytes=900-5000", ByteRange::Partial(900, 999)),
+            ("bytes=1000-", ByteRange::Unsatisfiable),
+            ("bytes=-0", ByteRange::Unsatisfiable),
+            ("bytes=5-1", ByteRange::Full),
+            ("bytes=0-1,5-9", ByteRange::Full),
+            ("items=0-1", ByteRange::Full),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_range(value, 1000), expected, "{}", value);
+        }
+    }
+}
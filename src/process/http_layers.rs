@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    Router,
+    body::Body,
+    http::{
+        Extensions, HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Version, header,
+    },
+    middleware,
+};
+use tower_http::{
+    compression::{CompressionLayer, Predicate, predicate::DefaultPredicate},
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer,
+};
+use tracing::{Span, info, info_span};
+
+use crate::{HttpOptions, http_auth};
+
+/// Wrap the routes in auth, access logs, CORS and compression, innermost
+/// first. CORS sits outside auth so preflight requests need no credentials,
+/// and the logs see sizes before compression.
+pub(crate) fn with_layers(router: Router, options: &HttpOptions) -> Result<Router> {
+    let mut router = router;
+    if options.auth.is_enabled() {
+        let auth = Arc::new(options.auth.clone());
+        router = router.layer(middleware::from_fn_with_state(auth, http_auth));
+    }
+    router = router.layer(
+        TraceLayer::new_for_http()
+            .make_span_with(|request: &Request<Body>| {
+                info_span!(
+                    "request",
+                    method = %request.method(),
+                    path = %request.uri().path(),
+                )
+            })
+            .on_response(|response: &Response<Body>, latency: Duration, _: &Span| {
+                let bytes = response
+                    .headers()
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok());
+                info!(
+                    status = response.status().as_u16(),
+                    bytes,
+                    latency_ms = latency.as_millis() as u64,
+                    "served"
+                );
+            }),
+    );
+    if !options.cors.is_empty() {
+        router = router.layer(cors_layer(&options.cors)?);
+    }
+    if options.compress {
+        let predicate = DefaultPredicate::new().and(is_whole_body);
+        router = router.layer(CompressionLayer::new().compress_when(predicate));
+    }
+    Ok(router)
+}
+
+/// `*` allows any origin, otherwise only the listed ones.
+fn cors_layer(origins: &[String]) -> Result<CorsLayer> {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| anyhow::anyhow!("Invalid CORS origin: {}", origin))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD, Method::POST, Method::PUT])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::RANGE,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            header::IF_RANGE,
+        ])
+        .expose_headers([
+            header::ACCEPT_RANGES,
+            header::CONTENT_LENGTH,
+            header::CONTENT_RANGE,
+            header::ETAG,
+            header::LAST_MODIFIED,
+        ]))
+}
+
+/// Byte ranges refer to the file as stored, compressing a part of it would
+/// make `Content-Range` meaningless.
+fn is_whole_body(status: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    status != StatusCode::PARTIAL_CONTENT && !headers.contains_key(header::CONTENT_RANGE)
+}
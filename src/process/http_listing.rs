@@ -0,0 +1,312 @@
+use std::{cmp::Ordering, fmt::Write, path::Path, time::SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::percent_encode;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// `?sort=size&order=desc` on a directory URL.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ListingQuery {
+    pub sort: SortKey,
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Dir,
+    File,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    pub size: u64,
+    /// RFC 3339 timestamp, missing when the file system doesn't record it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(skip)]
+    modified_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirListing {
+    /// URL path of the directory, always with a trailing slash
+    pub path: String,
+    pub entries: Vec<DirEntry>,
+    /// Decoded path segments, used for the breadcrumbs
+    #[serde(skip)]
+    segments: Vec<String>,
+}
+
+impl DirListing {
+    fn new(path: &str, entries: Vec<DirEntry>) -> Self {
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        Self {
+            path: url_path(&segments),
+            entries,
+            segments,
+        }
+    }
+}
+
+/// URL path for the given path segments, each one percent-encoded.
+fn url_path(segments: &[String]) -> String {
+    segments.iter().fold(String::from("/"), |mut url, segment| {
+        url.push_str(&percent_encode(segment));
+        url.push('/');
+        url
+    })
+}
+
+/// Read the entries of `dir`, which is served at the URL path `path`.
+pub async fn read_dir_listing(dir: &Path, path: &str, query: ListingQuery) -> Result<DirListing> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        // follow symlinks, but still list broken ones
+        let metadata = match tokio::fs::metadata(entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(_) => entry.metadata().await?,
+        };
+        let modified_at = metadata.modified().ok();
+        entries.push(DirEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            kind: if metadata.is_dir() {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            },
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: modified_at.map(|t| humantime::format_rfc3339_seconds(t).to_string()),
+            modified_at,
+        });
+    }
+    sort_entries(&mut entries, query);
+
+    Ok(DirListing::new(path, entries))
+}
+
+/// Directories always come first, ties are broken by name.
+fn sort_entries(entries: &mut [DirEntry], query: ListingQuery) {
+    entries.sort_by(|a, b| {
+        let ordering = match query.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified_at.cmp(&b.modified_at),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = match query.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        (b.kind == EntryKind::Dir)
+            .cmp(&(a.kind == EntryKind::Dir))
+            .then(ordering)
+    });
+}
+
+fn html_escape(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut out, c| {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                c => out.push(c),
+            }
+            out
+        })
+}
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+impl SortKey {
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+}
+
+/// A column header that sorts by `key`, clicking the current one flips the order.
+fn sort_link(title: &str, key: SortKey, query: ListingQuery) -> String {
+    let (order, arrow) = match (key == query.sort, query.order) {
+        (true, SortOrder::Asc) => ("desc", " ▲"),
+        (true, SortOrder::Desc) => ("asc", " ▼"),
+        (false, _) => ("asc", ""),
+    };
+    format!(
+        r#"<a href="?sort={}&amp;order={}">{}</a>{}"#,
+        key.name(),
+        order,
+        title,
+        arrow
+    )
+}
+
+/// `upload` adds a form posting files to the directory.
+pub fn render_listing_html(listing: &DirListing, query: ListingQuery, upload: bool) -> String {
+    let segments = &listing.segments;
+    let title = html_escape(&listing.path);
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Index of {title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.2em 1em; text-align: left; }}
+td.size {{ text-align: right; }}
+a {{ text-decoration: none; }}
+</style>
+</head>
+<body>
+<h1>"#
+    );
+
+    // breadcrumbs, every ancestor directory is a link
+    let _ = write!(html, r#"<a href="/">root</a>"#);
+    for i in 0..segments.len() {
+        let _ = write!(
+            html,
+            r#" / <a href="{}">{}</a>"#,
+            url_path(&segments[..=i]),
+            html_escape(&segments[i])
+        );
+    }
+    let _ = write!(
+        html,
+        "</h1>\n<table>\n<tr><th>{}</th><th>{}</th><th>{}</th></tr>\n",
+        sort_link("Name", SortKey::Name, query),
+        sort_link("Size", SortKey::Size, query),
+        sort_link("Modified", SortKey::Modified, query),
+    );
+    if !segments.is_empty() {
+        let parent = url_path(&segments[..segments.len() - 1]);
+        let _ = writeln!(
+            html,
+            r#"<tr><td><a href="{}">../</a></td><td></td><td></td></tr>"#,
+            parent
+        );
+    }
+    for entry in &listing.entries {
+        let (suffix, size) = match entry.kind {
+            EntryKind::Dir => ("/", "-".to_string()),
+            EntryKind::File => ("", human_size(entry.size)),
+        };
+        let _ = writeln!(
+            html,
+            r#"<tr><td><a href="{}{}{}">{}{}</a></td><td class="size">{}</td><td>{}</td></tr>"#,
+            listing.path,
+            percent_encode(&entry.name),
+            suffix,
+            html_escape(&entry.name),
+            suffix,
+            size,
+            entry.modified.as_deref().unwrap_or("-"),
+        );
+    }
+    html.push_str("</table>\n");
+    if upload {
+        let _ = writeln!(
+            html,
+            r#"<form method="post" action="{}" enctype="multipart/form-data">
+<p><input type="file" name="file" multiple required> <button type="submit">Upload</button></p>
+</form>"#,
+            listing.path
+        );
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, kind: EntryKind, size: u64) -> DirEntry {
+        DirEntry {
+            name: name.into(),
+            kind,
+            size,
+            modified: None,
+            modified_at: None,
+        }
+    }
+
+    #[test]
+    fn test_render_listing_html() {
+        let mut entries = vec![
+            entry("b.txt", EntryKind::File, 10),
+            entry("<a>.txt", EntryKind::File, 2048),
+            entry("src", EntryKind::Dir, 0),
+        ];
+        let query = ListingQuery {
+            sort: SortKey::Size,
+            order: SortOrder::Desc,
+        };
+        sort_entries(&mut entries, query);
+        let names = entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["src", "<a>.txt", "b.txt"]);
+
+        let listing = DirListing::new("docs/my notes", entries);
+        assert_eq!(listing.path, "/docs/my%20notes/");
+        let html = render_listing_html(&listing, query, true);
+        assert!(html.contains(
+            r#"<a href="/docs/">docs</a> / <a href="/docs/my%20notes/">my notes</a></h1>"#
+        ));
+        assert!(html.contains(r#"<a href="/docs/my%20notes/%3Ca%3E.txt">&lt;a&gt;.txt</a>"#));
+        assert!(html.contains(r#"<a href="/docs/">../</a>"#));
+        assert!(html.contains("2.0 KiB"));
+        assert!(html.contains(r#"<a href="?sort=size&amp;order=asc">Size</a> ▼"#));
+        assert!(html.contains(r#"<form method="post" action="/docs/my%20notes/""#));
+    }
+}
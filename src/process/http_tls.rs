@@ -0,0 +1,101 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use rustls::{
+    ServerConfig,
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, pem::PemObject},
+};
+use sha2::{Digest, Sha256};
+
+/// Where the server certificate comes from.
+#[derive(Debug, Clone)]
+pub enum TlsConfig {
+    /// PEM files with the certificate chain and its private key
+    Files { cert: PathBuf, key: PathBuf },
+    /// A certificate generated on startup for these subject alternative names
+    SelfSigned(Vec<String>),
+}
+
+/// A certificate chain along with its key, ready for rustls.
+#[derive(Debug)]
+pub struct TlsIdentity {
+    pub certs: Vec<CertificateDer<'static>>,
+    pub key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    pub fn load(config: &TlsConfig) -> Result<Self> {
+        match config {
+            TlsConfig::Files { cert, key } => {
+                let certs = CertificateDer::pem_file_iter(cert)
+                    .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                    .map_err(|e| {
+                        anyhow::anyhow!("Invalid certificate {}: {}", cert.display(), e)
+                    })?;
+                if certs.is_empty() {
+                    return Err(anyhow::anyhow!("No certificate in {}", cert.display()));
+                }
+                let key = PrivateKeyDer::from_pem_file(key)
+                    .map_err(|e| anyhow::anyhow!("Invalid private key {}: {}", key.display(), e))?;
+                Ok(Self { certs, key })
+            }
+            TlsConfig::SelfSigned(names) => Self::self_signed(names),
+        }
+    }
+
+    /// A fresh ECDSA P-256 key and certificate, kept in memory only.
+    pub fn self_signed(names: &[String]) -> Result<Self> {
+        let certified = rcgen::generate_simple_self_signed(names)?;
+        let key = PrivatePkcs8KeyDer::from(certified.signing_key.serialize_der());
+        Ok(Self {
+            certs: vec![certified.cert.der().clone()],
+            key: key.into(),
+        })
+    }
+
+    /// SHA-256 fingerprint of the leaf certificate, as browsers show it.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(&self.certs[0]);
+        digest
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    pub fn server_config(self) -> Result<Arc<ServerConfig>> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(self.certs, self.key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_identity() -> Result<()> {
+        let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let identity = TlsIdentity::self_signed(&names)?;
+        let fingerprint = identity.fingerprint();
+        assert_eq!(fingerprint.len(), 32 * 3 - 1);
+        assert!(fingerprint.split(':').all(|b| b.len() == 2));
+
+        // the PEM files a user would pass with --tls-cert/--tls-key
+        let dir = tempfile::tempdir()?;
+        let certified = rcgen::generate_simple_self_signed(names)?;
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        std::fs::write(&cert, certified.cert.pem())?;
+        std::fs::write(&key, certified.signing_key.serialize_pem())?;
+        let identity = TlsIdentity::load(&TlsConfig::Files { cert, key })?;
+        assert_eq!(identity.certs[0], *certified.cert.der());
+        assert!(identity.server_config().is_ok());
+
+        Ok(())
+    }
+}
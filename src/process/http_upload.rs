@@ -0,0 +1,267 @@
+use std::{
+    io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Multipart, Path, State},
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use crate::AppState;
+
+type UploadError = (StatusCode, String);
+
+/// The last path component of a client supplied file name, with control
+/// characters dropped and characters Windows can't store replaced. Returns
+/// `None` for names that can't be saved as they are, like `..`.
+pub fn sanitize_filename(name: &str, allow_hidden: bool) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." || name.len() > 255 {
+        return None;
+    }
+    if name.starts_with('.') && !allow_hidden {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Stream an upload to a temporary file next to `dest` and move it in place
+/// once complete, so readers never see a partial file. Returns whether an
+/// existing file was replaced.
+async fn save<S>(state: &AppState, dest: &FsPath, stream: S) -> Result<bool, UploadError>
+where
+    S: Stream<Item = Result<Bytes, UploadError>> + Unpin,
+{
+    let exists = tokio::fs::symlink_metadata(dest).await.ok();
+    match exists {
+        Some(metadata) if metadata.is_dir() => {
+            return Err((StatusCode::CONFLICT, "A directory has that name".into()));
+        }
+        Some(_) if !state.options.overwrite => {
+            return Err((StatusCode::CONFLICT, "File already exists".into()));
+        }
+        _ => {}
+    }
+
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dest.with_file_name(format!(".{}.{:08x}.part", name, rand::random::<u32>()));
+    let result = write_limited(&tmp, stream, state.options.max_upload_size).await;
+    let result = match result {
+        Ok(()) if state.options.overwrite => tokio::fs::rename(&tmp, dest).await.map_err(internal),
+        // the check above can race with another upload, linking fails
+        // instead of replacing a file that appeared meanwhile
+        Ok(()) => match tokio::fs::hard_link(&tmp, dest).await {
+            Ok(()) => tokio::fs::remove_file(&tmp).await.map_err(internal),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                Err((StatusCode::CONFLICT, "File already exists".into()))
+            }
+            Err(e) => Err(internal(e)),
+        },
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result.map(|()| exists.is_some())
+}
+
+async fn write_limited<S>(path: &FsPath, mut stream: S, limit: u64) -> Result<(), UploadError>
+where
+    S: Stream<Item = Result<Bytes, UploadError>> + Unpin,
+{
+    let mut file = tokio::fs::File::create(path).await.map_err(internal)?;
+    let mut written = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        written += chunk.len() as u64;
+        if written > limit {
+            return Err(too_large(limit));
+        }
+        file.write_all(&chunk).await.map_err(internal)?;
+    }
+    file.flush().await.map_err(internal)
+}
+
+fn internal(e: io::Error) -> UploadError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn too_large(limit: u64) -> UploadError {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Uploads are limited to {} bytes", limit),
+    )
+}
+
+/// The directory at `path`, uploads may only go into existing ones.
+async fn upload_dir(state: &AppState, path: &str) -> Result<PathBuf, UploadError> {
+    let dir = state.resolve(path).await.map_err(|status| {
+        let reason = status.canonical_reason().unwrap_or_default();
+        (status, format!("Upload directory: {}", reason))
+    })?;
+    if !dir.is_dir() {
+        return Err((StatusCode::BAD_REQUEST, "Not a directory".into()));
+    }
+    Ok(dir)
+}
+
+async fn save_multipart(
+    state: &AppState,
+    path: &str,
+    mut multipart: Multipart,
+) -> Result<Vec<String>, UploadError> {
+    let dir = upload_dir(state, path).await?;
+    let mut saved = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (e.status(), e.body_text()))?
+    {
+        // plain form fields have no file name
+        let Some(file_name) = field.file_name() else {
+            continue;
+        };
+        let name = sanitize_filename(file_name, state.options.show_hidden).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid file name: {}", file_name),
+        ))?;
+        let stream = std::pin::pin!(field.map_err(|e| (e.status(), e.body_text())));
+        save(state, &dir.join(&name), stream).await?;
+        info!("Uploaded {:?}", dir.join(&name));
+        saved.push(name);
+    }
+    if saved.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No file in the upload".into()));
+    }
+    Ok(saved)
+}
+
+async fn multipart_upload(
+    state: &AppState,
+    path: &str,
+    uri: &Uri,
+    multipart: Multipart,
+) -> (StatusCode, Response) {
+    match save_multipart(state, path, multipart).await {
+        // back to the listing the form was submitted from
+        Ok(_) => {
+            let location = match uri.path() {
+                p if p.ends_with('/') => p.to_string(),
+                p => format!("{}/", p),
+            };
+            (
+                StatusCode::SEE_OTHER,
+                Redirect::to(&location).into_response(),
+            )
+        }
+        Err((status, message)) => (status, message.into_response()),
+    }
+}
+
+pub(crate) async fn root_upload_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    multipart: Multipart,
+) -> (StatusCode, Response) {
+    multipart_upload(&state, "", &uri, multipart).await
+}
+
+pub(crate) async fn upload_handler(
+    State(state): State<Arc<AppState>>,
+    Path(path): Path<String>,
+    uri: Uri,
+    multipart: Multipart,
+) -> (StatusCode, Response) {
+    multipart_upload(&state, &path, &uri, multipart).await
+}
+
+async fn save_put(
+    state: &AppState,
+    path: &str,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<bool, UploadError> {
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if length.is_some_and(|length| length > state.options.max_upload_size) {
+        return Err(too_large(state.options.max_upload_size));
+    }
+
+    let (parent, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let name = sanitize_filename(file_name, state.options.show_hidden)
+        .filter(|name| name == file_name)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid file name: {}", file_name),
+        ))?;
+    let dir = upload_dir(state, parent).await?;
+    let stream = std::pin::pin!(
+        body.into_data_stream()
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+    );
+    let replaced = save(state, &dir.join(&name), stream).await?;
+    info!("Uploaded {:?}", dir.join(&name));
+    Ok(replaced)
+}
+
+pub(crate) async fn put_handler(
+    State(state): State<Arc<AppState>>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> (StatusCode, Response) {
+    match save_put(&state, &path, &headers, body).await {
+        Ok(true) => (StatusCode::NO_CONTENT, ().into_response()),
+        Ok(false) => (StatusCode::CREATED, "Created\n".into_response()),
+        Err((status, message)) => (status, message.into_response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        let cases = [
+            ("report.pdf", Some("report.pdf")),
+            ("../../etc/passwd", Some("passwd")),
+            ("C:\\Users\\me\\notes.txt", Some("notes.txt")),
+            ("what?<now>.txt", Some("what__now_.txt")),
+            ("line\nbreak.txt", Some("linebreak.txt")),
+            ("..", None),
+            ("dir/", None),
+            (".bashrc", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                sanitize_filename(name, false).as_deref(),
+                expected,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            sanitize_filename(".bashrc", true).as_deref(),
+            Some(".bashrc")
+        );
+    }
+}
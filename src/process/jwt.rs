@@ -0,0 +1,357 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use p256::ecdsa::Signature as P256Signature;
+use serde_json::{Map, Value, json};
+
+use crate::{Input, JwtAlg};
+
+use super::{
+    Decryptor, EcdsaP256, EcdsaP256Verifier, Ed25519, Ed25519Verifier, Encryptor, HmacSha256,
+    HmacSha512, Rsa, RsaPadding, RsaVerifier, is_pem, read_key,
+};
+
+#[derive(Debug, Clone)]
+pub struct JwtClaims {
+    pub sub: Option<String>,
+    pub aud: Option<String>,
+    pub iss: Option<String>,
+    /// Lifetime of the token, counted from now
+    pub exp: Duration,
+    pub extra: Vec<(String, String)>,
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+fn signer(alg: JwtAlg, key: &Input, passphrase: Option<&str>) -> Result<Box<dyn Encryptor>> {
+    let key = read_key(key, passphrase)?;
+    let signer: Box<dyn Encryptor> = match alg {
+        JwtAlg::HS256 => Box::new(HmacSha256::load_key(&key)?),
+        JwtAlg::HS512 => Box::new(HmacSha512::load_key(&key)?),
+        JwtAlg::EdDSA => Box::new(Ed25519::load_key(&key)?),
+        JwtAlg::ES256 => Box::new(EcdsaP256::load_key(&key)?),
+        JwtAlg::RS256 => Box::new(Rsa::load_key(&key, RsaPadding::Pkcs1v15)?),
+        JwtAlg::PS256 => Box::new(Rsa::load_key(&key, RsaPadding::Pss)?),
+    };
+    Ok(signer)
+}
+
+/// HMAC takes any bytes as its secret, a published public key among them.
+/// Refusing those stops tokens forged with the public half of a key pair.
+fn is_public_key(key: &Input, content: &[u8]) -> bool {
+    let public_file = matches!(key, Input::File(path)
+        if matches!(path.extension().and_then(|ext| ext.to_str()), Some("pk" | "pub")));
+    public_file || is_pem(content)
+}
+
+fn verifier(alg: JwtAlg, key: &Input, passphrase: Option<&str>) -> Result<Box<dyn Decryptor>> {
+    let content = read_key(key, passphrase)?;
+    if matches!(alg, JwtAlg::HS256 | JwtAlg::HS512) && is_public_key(key, &content) {
+        return Err(anyhow::anyhow!(
+            "{} needs a shared secret, not a public key",
+            <&str>::from(alg)
+        ));
+    }
+    let key = content;
+    let verifier: Box<dyn Decryptor> = match alg {
+        JwtAlg::HS256 => Box::new(HmacSha256::load_key(&key)?),
+        JwtAlg::HS512 => Box::new(HmacSha512::load_key(&key)?),
+        JwtAlg::EdDSA => Box::new(Ed25519Verifier::load_key(&key)?),
+        JwtAlg::ES256 => Box::new(EcdsaP256Verifier::load_key(&key)?),
+        JwtAlg::RS256 => Box::new(RsaVerifier::load_key(&key, RsaPadding::Pkcs1v15)?),
+        JwtAlg::PS256 => Box::new(RsaVerifier::load_key(&key, RsaPadding::Pss)?),
+    };
+    Ok(verifier)
+}
+
+fn encode_segment(value: &Value) -> Result<String> {
+    Ok(BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(value)?))
+}
+
+fn decode_segment(segment: &str) -> Result<Value> {
+    let json = BASE64_URL_SAFE_NO_PAD.decode(segment)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+fn split_token(token: &str) -> Result<(&str, &str, &str)> {
+    let mut parts = token.trim().splitn(3, '.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(claims), Some(sig)) if !sig.contains('.') => Ok((header, claims, sig)),
+        _ => Err(anyhow::anyhow!(
+            "Invalid JWT: expected three dot separated parts"
+        )),
+    }
+}
+
+pub fn process_jwt_sign(
+    claims: &JwtClaims,
+    alg: JwtAlg,
+    key: &Input,
+    passphrase: Option<&str>,
+) -> Result<String> {
+    let iat = now()?;
+    let mut payload = Map::new();
+    for (name, value) in [
+        ("sub", &claims.sub),
+        ("aud", &claims.aud),
+        ("iss", &claims.iss),
+    ] {
+        if let Some(value) = value {
+            payload.insert(name.into(), value.clone().into());
+        }
+    }
+    payload.insert("iat".into(), iat.into());
+    payload.insert(
+        "exp".into(),
+        iat.saturating_add(claims.exp.as_secs()).into(),
+    );
+    for (name, value) in &claims.extra {
+        payload.insert(name.clone(), value.clone().into());
+    }
+
+    let alg_name: &'static str = alg.into();
+    let header = json!({ "alg": alg_name, "typ": "JWT" });
+    let signing_input = format!(
+        "{}.{}",
+        encode_segment(&header)?,
+        encode_segment(&payload.into())?
+    );
+
+    let mut sig = signer(alg, key, passphrase)?.encrypt(signing_input.as_bytes())?;
+    if alg == JwtAlg::ES256 {
+        // JWS wants the fixed size `r || s` form instead of DER
+        sig = P256Signature::from_der(&sig)?.to_bytes().to_vec();
+    }
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        BASE64_URL_SAFE_NO_PAD.encode(sig)
+    ))
+}
+
+pub fn process_jwt_decode(token: &str) -> Result<(Value, Value)> {
+    let (header, claims, _) = split_token(token)?;
+    Ok((decode_segment(header)?, decode_segment(claims)?))
+}
+
+/// Check the signature and the registered claims, returning the claims of a
+/// valid token. `alg` comes from the caller, never from the token itself, so a
+/// token can't pick how it gets verified.
+pub fn process_jwt_verify(
+    token: &str,
+    alg: JwtAlg,
+    key: &Input,
+    passphrase: Option<&str>,
+    aud: Option<&str>,
+    iss: Option<&str>,
+    leeway: Duration,
+) -> Result<Value> {
+    let (header_segment, claims_segment, sig) = split_token(token)?;
+    let header = decode_segment(header_segment)?;
+    let token_alg: JwtAlg = header["alg"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("JWT header has no alg"))?
+        .parse()?;
+    if alg != token_alg {
+        return Err(anyhow::anyhow!(
+            "Unexpected JWT algorithm {}",
+            <&str>::from(token_alg)
+        ));
+    }
+
+    let signing_input = format!("{}.{}", header_segment, claims_segment);
+    let sig = BASE64_URL_SAFE_NO_PAD.decode(sig)?;
+    if !verifier(alg, key, passphrase)?.decrypt(signing_input.as_bytes(), &sig)? {
+        return Err(anyhow::anyhow!("Invalid JWT signature"));
+    }
+
+    let claims = decode_segment(claims_segment)?;
+    let now = now()?;
+    let leeway = leeway.as_secs();
+    if let Some(exp) = claims.get("exp") {
+        let exp = exp
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid exp claim"))?;
+        if now > exp.saturating_add(leeway) {
+            return Err(anyhow::anyhow!("JWT has expired"));
+        }
+    }
+    if let Some(nbf) = claims.get("nbf") {
+        let nbf = nbf
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid nbf claim"))?;
+        if now.saturating_add(leeway) < nbf {
+            return Err(anyhow::anyhow!("JWT is not valid yet"));
+        }
+    }
+    if let Some(aud) = aud {
+        // aud may be a single string or an array of strings
+        let matches = match &claims["aud"] {
+            Value::String(claim) => claim == aud,
+            Value::Array(claim) => claim.iter().any(|claim| claim == aud),
+            _ => false,
+        };
+        if !matches {
+            return Err(anyhow::anyhow!("JWT audience does not match {}", aud));
+        }
+    }
+    if let Some(iss) = iss
+        && claims["iss"] != iss
+    {
+        return Err(anyhow::anyhow!("JWT issuer does not match {}", iss));
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+    fn secret() -> Input {
+        Input::Literal(SECRET.into())
+    }
+
+    fn claims(exp: Duration) -> JwtClaims {
+        JwtClaims {
+            sub: Some("alice".into()),
+            aud: Some("svc".into()),
+            iss: None,
+            exp,
+            extra: vec![("role".into(), "admin".into())],
+        }
+    }
+
+    #[test]
+    fn test_jwt_sign_verify() -> Result<()> {
+        let leeway = Duration::from_secs(60);
+        let token = process_jwt_sign(
+            &claims(Duration::from_secs(3600)),
+            JwtAlg::HS256,
+            &secret(),
+            None,
+        )?;
+        let verified = process_jwt_verify(
+            &token,
+            JwtAlg::HS256,
+            &secret(),
+            None,
+            Some("svc"),
+            None,
+            leeway,
+        )?;
+        assert_eq!(verified["sub"], "alice");
+        assert_eq!(verified["role"], "admin");
+
+        assert!(
+            process_jwt_verify(
+                &token,
+                JwtAlg::HS256,
+                &secret(),
+                None,
+                Some("other"),
+                None,
+                leeway
+            )
+            .is_err()
+        );
+        assert!(
+            process_jwt_verify(&token, JwtAlg::HS512, &secret(), None, None, None, leeway).is_err()
+        );
+        assert!(
+            process_jwt_verify(
+                &token,
+                JwtAlg::HS256,
+                &Input::Literal(b"another secret".to_vec()),
+                None,
+                None,
+                None,
+                leeway
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_eddsa_with_key_files() -> Result<()> {
+        let token = process_jwt_sign(
+            &claims(Duration::from_secs(60)),
+            JwtAlg::EdDSA,
+            &Input::File("fixtures/ed25519.sk".into()),
+            None,
+        )?;
+        let (header, _) = process_jwt_decode(&token)?;
+        assert_eq!(header["alg"], "EdDSA");
+
+        let verified = process_jwt_verify(
+            &token,
+            JwtAlg::EdDSA,
+            &Input::File("fixtures/ed25519.pk".into()),
+            None,
+            None,
+            None,
+            Duration::ZERO,
+        )?;
+        assert_eq!(verified["aud"], "svc");
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_expired() -> Result<()> {
+        let header = encode_segment(&json!({ "alg": "HS256", "typ": "JWT" }))?;
+        let claims = encode_segment(&json!({ "sub": "alice", "exp": now()? - 120 }))?;
+        let signing_input = format!("{}.{}", header, claims);
+        let sig = HmacSha256::try_new(SECRET)?.encrypt(signing_input.as_bytes())?;
+        let token = format!("{}.{}", signing_input, BASE64_URL_SAFE_NO_PAD.encode(sig));
+
+        let leeway = Duration::from_secs(60);
+        assert!(
+            process_jwt_verify(&token, JwtAlg::HS256, &secret(), None, None, None, leeway).is_err()
+        );
+        assert!(process_jwt_decode(&token).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_rejects_hmac_with_public_key() -> Result<()> {
+        // a token "signed" with the published RSA key as the HMAC secret
+        let public_key = Input::File("fixtures/rsa.pk".into());
+        let header = encode_segment(&json!({ "alg": "HS256", "typ": "JWT" }))?;
+        let claims = encode_segment(&json!({ "sub": "mallory", "exp": now()? + 60 }))?;
+        let signing_input = format!("{}.{}", header, claims);
+        let sig = HmacSha256::load_key(std::fs::read("fixtures/rsa.pk")?)?
+            .encrypt(signing_input.as_bytes())?;
+        let token = format!("{}.{}", signing_input, BASE64_URL_SAFE_NO_PAD.encode(sig));
+
+        let leeway = Duration::ZERO;
+        for alg in [JwtAlg::RS256, JwtAlg::PS256, JwtAlg::HS256] {
+            assert!(
+                process_jwt_verify(&token, alg, &public_key, None, None, None, leeway).is_err()
+            );
+        }
+        let pem = Input::Literal(std::fs::read("fixtures/rsa.pk")?);
+        let result = process_jwt_verify(&token, JwtAlg::HS256, &pem, None, None, None, leeway);
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_far_future_exp() -> Result<()> {
+        let header = encode_segment(&json!({ "alg": "HS256", "typ": "JWT" }))?;
+        let claims = encode_segment(&json!({ "sub": "alice", "exp": u64::MAX }))?;
+        let signing_input = format!("{}.{}", header, claims);
+        let sig = HmacSha256::try_new(SECRET)?.encrypt(signing_input.as_bytes())?;
+        let token = format!("{}.{}", signing_input, BASE64_URL_SAFE_NO_PAD.encode(sig));
+
+        let leeway = Duration::from_secs(60);
+        let claims =
+            process_jwt_verify(&token, JwtAlg::HS256, &secret(), None, None, None, leeway)?;
+        assert_eq!(claims["exp"], u64::MAX);
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    fmt, fs,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use argon2::Argon2;
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, rand_core::RngCore},
+};
+
+use crate::{EncryptFormat, Input};
+
+use super::{
+    Blake3, EcdsaP256, EcdsaP256Verifier, Ed25519, Ed25519Verifier, HmacSha256, HmacSha512,
+    KeyFingerprint, KeyLoader, Rsa, RsaVerifier, rsa_padding,
+};
+
+/// Header of a passphrase-encrypted key file, followed by the argon2 salt, the
+/// nonce and the ChaCha20-Poly1305 ciphertext of the raw key.
+const SEALED_KEY_MAGIC: &[u8] = b"RCLIKEY1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    Shared,
+    Secret,
+    Public,
+}
+
+#[derive(Debug)]
+pub struct KeyInfo {
+    pub algorithm: &'static str,
+    pub kind: KeyKind,
+    pub fingerprint: String,
+    /// Derived public key, only present for secret keys.
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            KeyKind::Shared => "shared",
+            KeyKind::Secret => "secret",
+            KeyKind::Public => "public",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl fmt::Display for KeyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "algorithm:   {}", self.algorithm)?;
+        writeln!(f, "type:        {}", self.kind)?;
+        write!(f, "fingerprint: {}", self.fingerprint)?;
+        if let Some(pk) = &self.public_key {
+            write!(f, "\npublic key:  {}", BASE64_URL_SAFE_NO_PAD.encode(pk))?;
+        }
+        Ok(())
+    }
+}
+
+/// Public keys are told apart from secret keys by their file extension, the
+/// same `.sk` / `.pk` naming `crypto generate` uses, or by their PEM label.
+fn is_public_key_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("pk" | "pub")
+    ) || fs::read_to_string(path).is_ok_and(|pem| pem.contains("PUBLIC KEY-----"))
+}
+
+fn shared_key_info(format: &EncryptFormat, key: impl KeyFingerprint) -> KeyInfo {
+    KeyInfo {
+        algorithm: format.into(),
+        kind: KeyKind::Shared,
+        fingerprint: key.fingerprint(),
+        public_key: None,
+    }
+}
+
+fn public_key_info(format: &EncryptFormat, key: impl KeyFingerprint) -> KeyInfo {
+    KeyInfo {
+        algorithm: format.into(),
+        kind: KeyKind::Public,
+        fingerprint: key.fingerprint(),
+        public_key: None,
+    }
+}
+
+fn secret_key_info(format: &EncryptFormat, key: impl KeyFingerprint, pk: Vec<u8>) -> KeyInfo {
+    KeyInfo {
+        algorithm: format.into(),
+        kind: KeyKind::Secret,
+        fingerprint: key.fingerprint(),
+        public_key: Some(pk),
+    }
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive key from passphrase: {}", e))?;
+    Ok(ChaCha20Poly1305::new(&key.into()))
+}
+
+pub fn is_sealed_key(data: &[u8]) -> bool {
+    data.starts_with(SEALED_KEY_MAGIC)
+}
+
+/// Encrypt a raw key with a passphrase so it can be stored on disk.
+pub fn seal_key(key: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = derive_cipher(passphrase, &salt)?
+        .encrypt(&nonce, key)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt key"))?;
+
+    let mut sealed =
+        Vec::with_capacity(SEALED_KEY_MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(SEALED_KEY_MAGIC);
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Return the raw key, decrypting it first if it was sealed with [`seal_key`].
+pub fn open_key(data: Vec<u8>, passphrase: Option<&str>) -> Result<Vec<u8>> {
+    if !is_sealed_key(&data) {
+        return Ok(data);
+    }
+    let passphrase =
+        passphrase.ok_or_else(|| anyhow::anyhow!("Key is encrypted, a passphrase is required"))?;
+
+    let data = &data[SEALED_KEY_MAGIC.len()..];
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(anyhow::anyhow!("Encrypted key is truncated"));
+    }
+    let (salt, data) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    derive_cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt key, wrong passphrase?"))
+}
+
+pub fn read_key_file(path: impl AsRef<Path>, passphrase: Option<&str>) -> Result<Vec<u8>> {
+    open_key(fs::read(path)?, passphrase)
+}
+
+/// Read key material from wherever `--key` points to.
+pub fn read_key(key: &Input, passphrase: Option<&str>) -> Result<Vec<u8>> {
+    open_key(key.read(false)?, passphrase)
+}
+
+/// Write a key to disk without clobbering an existing one unless `force` is
+/// set. Secret keys are only readable by the owner.
+pub fn write_key_file(
+    path: impl AsRef<Path>,
+    content: &[u8],
+    secret: bool,
+    force: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => anyhow::anyhow!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        ),
+        _ => e.into(),
+    })?;
+    // the mode above only applies to newly created files
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content)?;
+    Ok(())
+}
+
+pub fn process_key_info(
+    path: impl AsRef<Path>,
+    format: &EncryptFormat,
+    passphrase: Option<&str>,
+) -> Result<KeyInfo> {
+    let path = path.as_ref();
+    let public = is_public_key_path(path);
+    let info = match format {
+        EncryptFormat::Blake3 => {
+            shared_key_info(format, Blake3::load_with_passphrase(path, passphrase)?)
+        }
+        EncryptFormat::HmacSha256 => {
+            shared_key_info(format, HmacSha256::load_with_passphrase(path, passphrase)?)
+        }
+        EncryptFormat::HmacSha512 => {
+            shared_key_info(format, HmacSha512::load_with_passphrase(path, passphrase)?)
+        }
+        EncryptFormat::Ed25519 if public => public_key_info(
+            format,
+            Ed25519Verifier::load_with_passphrase(path, passphrase)?,
+        ),
+        EncryptFormat::Ed25519 => {
+            let key = Ed25519::load_with_passphrase(path, passphrase)?;
+            let pk = key.public_key();
+            secret_key_info(format, key, pk)
+        }
+        EncryptFormat::EcdsaP256 if public => public_key_info(
+            format,
+            EcdsaP256Verifier::load_with_passphrase(path, passphrase)?,
+        ),
+        EncryptFormat::EcdsaP256 => {
+            let key = EcdsaP256::load_with_passphrase(path, passphrase)?;
+            let pk = key.public_key()?;
+            secret_key_info(format, key, pk)
+        }
+        EncryptFormat::RsaPss | EncryptFormat::RsaPkcs1 if public => public_key_info(
+            format,
+            RsaVerifier::load(path, passphrase, rsa_padding(format))?,
+        ),
+        EncryptFormat::RsaPss | EncryptFormat::RsaPkcs1 => {
+            let key = Rsa::load(path, passphrase, rsa_padding(format))?;
+            let pk = key.public_key()?;
+            secret_key_info(format, key, pk)
+        }
+    };
+    Ok(info)
+}
+
+pub fn process_key_pub(
+    path: impl AsRef<Path>,
+    format: &EncryptFormat,
+    passphrase: Option<&str>,
+) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    match format {
+        EncryptFormat::Blake3 | EncryptFormat::HmacSha256 | EncryptFormat::HmacSha512 => {
+            Err(anyhow::anyhow!(
+                "{} keys are shared secrets without a public key",
+                <&str>::from(format)
+            ))
+        }
+        EncryptFormat::Ed25519 => Ok(Ed25519::load_with_passphrase(path, passphrase)?.public_key()),
+        EncryptFormat::EcdsaP256 => EcdsaP256::load_with_passphrase(path, passphrase)?.public_key(),
+        EncryptFormat::RsaPss | EncryptFormat::RsaPkcs1 => {
+            Rsa::load(path, passphrase, rsa_padding(format))?.public_key()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RsaPadding, process_generate};
+
+    use super::*;
+
+    #[test]
+    fn test_key_info_pairs_share_fingerprint() -> Result<()> {
+        let sk = process_key_info("fixtures/ed25519.sk", &EncryptFormat::Ed25519, None)?;
+        let pk = process_key_info("fixtures/ed25519.pk", &EncryptFormat::Ed25519, None)?;
+
+        assert_eq!(sk.kind, KeyKind::Secret);
+        assert_eq!(pk.kind, KeyKind::Public);
+        assert_eq!(sk.fingerprint, pk.fingerprint);
+        assert_eq!(sk.public_key, Some(std::fs::read("fixtures/ed25519.pk")?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_pub() -> Result<()> {
+        let pk = process_key_pub("fixtures/ed25519.sk", &EncryptFormat::Ed25519, None)?;
+        assert_eq!(pk, std::fs::read("fixtures/ed25519.pk")?);
+
+        let pk = process_key_pub("fixtures/rsa.sk", &EncryptFormat::RsaPss, None)?;
+        let expected = RsaVerifier::load("fixtures/rsa.pk", None, RsaPadding::Pss)?;
+        assert_eq!(pk, expected.public_key()?);
+
+        let dir = tempfile::tempdir()?;
+        let key = process_generate(&EncryptFormat::EcdsaP256)?;
+        let (sk, pk_path) = (dir.path().join("p256.sk"), dir.path().join("p256.pk"));
+        std::fs::write(&sk, &key[0])?;
+        std::fs::write(&pk_path, &key[1])?;
+        let pk = process_key_pub(&sk, &EncryptFormat::EcdsaP256, None)?;
+        assert_eq!(pk, EcdsaP256Verifier::load(&pk_path)?.public_key()?);
+
+        assert!(process_key_pub(&sk, &EncryptFormat::Blake3, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_open_key() -> Result<()> {
+        let key = fs::read("fixtures/ed25519.sk")?;
+        let sealed = seal_key(&key, "correct horse")?;
+
+        assert!(is_sealed_key(&sealed));
+        assert!(open_key(sealed.clone(), None).is_err());
+        assert!(open_key(sealed.clone(), Some("battery staple")).is_err());
+        assert_eq!(open_key(sealed, Some("correct horse"))?, key);
+        // plaintext keys pass through untouched
+        assert_eq!(open_key(key.clone(), Some("correct horse"))?, key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_key_file_refuses_overwrite() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-key-{}.sk", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        write_key_file(&path, b"first", true, false)?;
+        assert!(write_key_file(&path, b"second", true, false).is_err());
+        write_key_file(&path, b"second", true, true)?;
+        assert_eq!(fs::read(&path)?, b"second");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use std::{fmt, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentType {
+    pub mime: &'static str,
+    pub extension: &'static str,
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mime)
+    }
+}
+
+const fn content_type(mime: &'static str, extension: &'static str) -> ContentType {
+    ContentType { mime, extension }
+}
+
+pub const OCTET_STREAM: ContentType = content_type("application/octet-stream", "bin");
+pub const JSON: ContentType = content_type("application/json", "json");
+pub const TEXT: ContentType = content_type("text/plain", "txt");
+
+/// Leading bytes of well known binary formats.
+const MAGIC: &[(&[u8], ContentType)] = &[
+    (b"\x89PNG\r\n\x1a\n", content_type("image/png", "png")),
+    (b"\xff\xd8\xff", content_type("image/jpeg", "jpg")),
+    (b"GIF87a", content_type("image/gif", "gif")),
+    (b"GIF89a", content_type("image/gif", "gif")),
+    (b"\x00\x00\x01\x00", content_type("image/x-icon", "ico")),
+    (b"%PDF-", content_type("application/pdf", "pdf")),
+    (b"\x1f\x8b", content_type("application/gzip", "gz")),
+    (b"\x28\xb5\x2f\xfd", content_type("application/zstd", "zst")),
+    (b"BZh", content_type("application/x-bzip2", "bz2")),
+    (b"\xfd7zXZ\x00", content_type("application/x-xz", "xz")),
+    (b"PK\x03\x04", content_type("application/zip", "zip")),
+    (b"\x00asm", content_type("application/wasm", "wasm")),
+    (b"wOFF", content_type("font/woff", "woff")),
+    (b"wOF2", content_type("font/woff2", "woff2")),
+    (b"\x7fELF", content_type("application/x-elf", "elf")),
+];
+
+/// Types recognized by file extension, the first entry for a MIME type gives
+/// its preferred extension.
+const EXTENSIONS: &[ContentType] = &[
+    content_type("text/html", "html"),
+    content_type("text/html", "htm"),
+    content_type("text/css", "css"),
+    content_type("text/javascript", "js"),
+    content_type("text/javascript", "mjs"),
+    content_type("text/csv", "csv"),
+    content_type("text/markdown", "md"),
+    content_type("application/xml", "xml"),
+    content_type("application/yaml", "yaml"),
+    content_type("application/yaml", "yml"),
+    JSON,
+    TEXT,
+    content_type("image/png", "png"),
+    content_type("image/jpeg", "jpg"),
+    content_type("image/jpeg", "jpeg"),
+    content_type("image/gif", "gif"),
+    content_type("image/webp", "webp"),
+    content_type("image/svg+xml", "svg"),
+    content_type("image/x-icon", "ico"),
+    content_type("image/avif", "avif"),
+    content_type("font/woff", "woff"),
+    content_type("font/woff2", "woff2"),
+    content_type("font/ttf", "ttf"),
+    content_type("font/otf", "otf"),
+    content_type("audio/mpeg", "mp3"),
+    content_type("video/mp4", "mp4"),
+    content_type("video/webm", "webm"),
+    content_type("application/pdf", "pdf"),
+    content_type("application/wasm", "wasm"),
+    content_type("application/zip", "zip"),
+    content_type("application/gzip", "gz"),
+    OCTET_STREAM,
+];
+
+pub fn content_type_from_extension(extension: &str) -> Option<ContentType> {
+    EXTENSIONS
+        .iter()
+        .find(|ct| ct.extension.eq_ignore_ascii_case(extension))
+        .copied()
+}
+
+/// Look up a MIME type, ignoring parameters such as `;charset=utf-8`.
+pub fn content_type_from_mime(mime: &str) -> Option<ContentType> {
+    let mime = mime.split(';').next().unwrap_or_default().trim();
+    EXTENSIONS
+        .iter()
+        .chain(MAGIC.iter().map(|(_, ct)| ct))
+        .find(|ct| ct.mime.eq_ignore_ascii_case(mime))
+        .copied()
+}
+
+/// Use the file extension when it is a known one, otherwise sniff the content.
+pub fn guess_content_type(path: Option<&Path>, data: &[u8]) -> ContentType {
+    path.and_then(|path| path.extension())
+        .and_then(|ext| content_type_from_extension(&ext.to_string_lossy()))
+        .unwrap_or_else(|| sniff_content_type(data))
+}
+
+/// Guess the content type from magic bytes, falling back to JSON, UTF-8 text
+/// and finally `application/octet-stream`.
+pub fn sniff_content_type(data: &[u8]) -> ContentType {
+    if let Some((_, content_type)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return *content_type;
+    }
+    // RIFF containers carry their actual type after the chunk size
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return content_type("image/webp", "webp");
+    }
+
+    let Ok(text) = std::str::from_utf8(data) else {
+        return OCTET_STREAM;
+    };
+    let text = text.trim_start();
+    if text.starts_with(['{', '[']) && serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        JSON
+    } else if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+        content_type("image/svg+xml", "svg")
+    } else {
+        TEXT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_content_type() {
+        let cases: [(&[u8], &str); 6] = [
+            (b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR", "image/png"),
+            (b"\x1f\x8b\x08\x00\x00\x00\x00\x00", "application/gzip"),
+            (b"RIFF\x24\x00\x00\x00WEBPVP8 ", "image/webp"),
+            (b" {\"name\": \"rcli\"}\n", "application/json"),
+            ("héllo".as_bytes(), "text/plain"),
+            (b"\xde\xad\xbe\xef", "application/octet-stream"),
+        ];
+        for (data, mime) in cases {
+            assert_eq!(sniff_content_type(data).mime, mime);
+        }
+    }
+
+    #[test]
+    fn test_guess_content_type() {
+        let css = guess_content_type(Some(Path::new("site.CSS")), b"body {}");
+        assert_eq!(css.mime, "text/css");
+        let png = guess_content_type(Some(Path::new("logo")), b"\x89PNG\r\n\x1a\n");
+        assert_eq!(png.extension, "png");
+        let jpeg = content_type_from_mime("image/jpeg; q=1").unwrap();
+        assert_eq!(jpeg.extension, "jpg");
+    }
+}
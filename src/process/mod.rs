@@ -1,11 +1,36 @@
 mod base64;
 mod crypto;
 mod csv;
+mod hash;
 mod http;
+mod http_auth;
+mod http_file;
+mod http_layers;
+mod http_listing;
+mod http_tls;
+mod http_upload;
+mod jwt;
+mod key;
+mod mime;
+mod otp;
 mod passwd;
+mod passwd_hash;
+mod strength;
 
 pub use base64::*;
 pub use crypto::*;
 pub use csv::*;
+pub use hash::*;
 pub use http::*;
+pub use http_auth::*;
+pub use http_file::*;
+pub use http_listing::*;
+pub use http_tls::*;
+pub use http_upload::*;
+pub use jwt::*;
+pub use key::*;
+pub use mime::*;
+pub use otp::*;
 pub use passwd::*;
+pub use passwd_hash::*;
+pub use strength::*;
@@ -0,0 +1,229 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use hmac::{Mac, SimpleHmac, digest::core_api::BlockSizeUser};
+use qrcode::{QrCode, render::unicode::Dense1x2};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    EncodeOptions, Encoding, OtpAlgo, percent_encode, process_decode, process_encode, secure_eq,
+};
+
+#[derive(Debug, Clone)]
+pub struct OtpParams {
+    pub secret: Vec<u8>,
+    pub algo: OtpAlgo,
+    pub digits: u32,
+    /// TOTP time step in seconds
+    pub period: u64,
+}
+
+impl OtpParams {
+    /// Build the parameters from a base32 secret as shown by authenticator
+    /// setup pages, spaces, lowercase and missing padding are fine.
+    pub fn try_new(secret: &str, algo: OtpAlgo, digits: u32, period: u64) -> Result<Self> {
+        let secret = process_decode(secret.as_bytes(), Encoding::Base32)
+            .map_err(|e| anyhow::anyhow!("Invalid base32 secret: {}", e))?;
+        if secret.is_empty() {
+            return Err(anyhow::anyhow!("OTP secret must not be empty"));
+        }
+        if period == 0 {
+            return Err(anyhow::anyhow!("OTP period must be at least 1 second"));
+        }
+        Ok(Self {
+            secret,
+            algo,
+            digits,
+            period,
+        })
+    }
+}
+
+fn mac<D: Digest + BlockSizeUser>(key: &[u8], counter: u64) -> Vec<u8> {
+    let mut mac =
+        <SimpleHmac<D> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// HOTP as defined in RFC 4226, with the hash of RFC 6238 selectable.
+pub fn hotp(params: &OtpParams, counter: u64) -> String {
+    let hash = match params.algo {
+        OtpAlgo::Sha1 => mac::<Sha1>(&params.secret, counter),
+        OtpAlgo::Sha256 => mac::<Sha256>(&params.secret, counter),
+        OtpAlgo::Sha512 => mac::<Sha512>(&params.secret, counter),
+    };
+    // dynamic truncation: the low nibble of the last byte picks 4 bytes
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin =
+        u32::from_be_bytes(hash[offset..offset + 4].try_into().expect("4 bytes")) & 0x7fff_ffff;
+    let code = bin as u64 % 10u64.pow(params.digits);
+    format!("{:0width$}", code, width = params.digits as usize)
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// A random secret of `bytes` length, base32 encoded without padding.
+pub fn process_otp_secret(bytes: usize) -> Result<String> {
+    let mut secret = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let options = EncodeOptions {
+        padding: Some(false),
+        ..Default::default()
+    };
+    process_encode(&secret, Encoding::Base32, &options)
+}
+
+/// The HOTP code for `counter`, or the current TOTP code along with the
+/// number of seconds it stays valid.
+pub fn process_otp_code(params: &OtpParams, counter: Option<u64>) -> Result<(String, Option<u64>)> {
+    if let Some(counter) = counter {
+        return Ok((hotp(params, counter), None));
+    }
+    let now = now()?;
+    let code = hotp(params, now / params.period);
+    Ok((code, Some(params.period - now % params.period)))
+}
+
+/// Check `code` against the steps (or HOTP counters) within `skew` of the
+/// current one and return the offset of the matching step.
+pub fn process_otp_verify(
+    params: &OtpParams,
+    code: &str,
+    counter: Option<u64>,
+    skew: u64,
+) -> Result<Option<i64>> {
+    let code = code.trim();
+    let (current, range) = match counter {
+        // HOTP clients only ever move forward
+        Some(counter) => (counter, 0..=skew as i64),
+        None => (now()? / params.period, -(skew as i64)..=skew as i64),
+    };
+    let matched = range.into_iter().find(|offset| {
+        current
+            .checked_add_signed(*offset)
+            .is_some_and(|counter| secure_eq(hotp(params, counter), code))
+    });
+    Ok(matched)
+}
+
+/// Key URI in the `otpauth://` format understood by authenticator apps.
+pub fn process_otp_uri(
+    params: &OtpParams,
+    issuer: Option<&str>,
+    account: &str,
+    counter: Option<u64>,
+) -> Result<String> {
+    let label = match issuer {
+        Some(issuer) => format!("{}:{}", percent_encode(issuer), percent_encode(account)),
+        None => percent_encode(account),
+    };
+    let options = EncodeOptions {
+        padding: Some(false),
+        ..Default::default()
+    };
+    let secret = process_encode(&params.secret, Encoding::Base32, &options)?;
+    let mut uri = format!(
+        "otpauth://{}/{}?secret={}&algorithm={}&digits={}",
+        if counter.is_some() { "hotp" } else { "totp" },
+        label,
+        secret,
+        <&str>::from(params.algo).to_uppercase(),
+        params.digits
+    );
+    match counter {
+        Some(counter) => uri.push_str(&format!("&counter={}", counter)),
+        None => uri.push_str(&format!("&period={}", params.period)),
+    }
+    if let Some(issuer) = issuer {
+        uri.push_str(&format!("&issuer={}", percent_encode(issuer)));
+    }
+    Ok(uri)
+}
+
+/// Render `data` as a QR code with unicode half blocks for the terminal.
+pub fn render_qr(data: &str) -> Result<String> {
+    let code = QrCode::new(data)?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(secret: &[u8], algo: OtpAlgo, digits: u32) -> OtpParams {
+        OtpParams {
+            secret: secret.to_vec(),
+            algo,
+            digits,
+            period: 30,
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc4226() {
+        let params = params(b"12345678901234567890", OtpAlgo::Sha1, 6);
+        let expected = ["755224", "287082", "359152", "969429", "338314"];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(&params, counter as u64), *code);
+        }
+    }
+
+    #[test]
+    fn test_totp_rfc6238() {
+        // T = 59s, the first test vector for each hash
+        let cases = [
+            (&b"12345678901234567890"[..], OtpAlgo::Sha1, "94287082"),
+            (
+                &b"12345678901234567890123456789012"[..],
+                OtpAlgo::Sha256,
+                "46119246",
+            ),
+            (
+                &b"1234567890123456789012345678901234567890123456789012345678901234"[..],
+                OtpAlgo::Sha512,
+                "90693936",
+            ),
+        ];
+        for (secret, algo, expected) in cases {
+            assert_eq!(hotp(&params(secret, algo, 8), 59 / 30), expected);
+        }
+    }
+
+    #[test]
+    fn test_otp_uri_and_verify() -> Result<()> {
+        let secret = process_otp_secret(20)?;
+        assert_eq!(secret.len(), 32);
+        let params = OtpParams::try_new(&secret.to_lowercase(), OtpAlgo::Sha1, 6, 30)?;
+
+        let (code, valid_for) = process_otp_code(&params, None)?;
+        assert!(valid_for.is_some_and(|secs| secs <= 30));
+        assert!(process_otp_verify(&params, &code, None, 1)?.is_some());
+        assert_eq!(
+            process_otp_verify(&params, &hotp(&params, 3), Some(1), 2)?,
+            Some(2)
+        );
+        assert_eq!(
+            process_otp_verify(&params, &hotp(&params, 0), Some(1), 2)?,
+            None
+        );
+
+        let uri = process_otp_uri(&params, Some("ACME Co"), "alice@example.com", None)?;
+        assert_eq!(
+            uri,
+            format!(
+                "otpauth://totp/ACME%20Co:alice%40example.com?secret={}&algorithm=SHA1&digits=6&period=30&issuer=ACME%20Co",
+                secret
+            )
+        );
+        Ok(())
+    }
+}
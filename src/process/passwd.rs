@@ -1,9 +1,148 @@
-use rand::seq::{IteratorRandom, SliceRandom};
+use std::{fs, path::Path};
+
+use rand::{
+    Rng,
+    seq::{IteratorRandom, SliceRandom},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{PasswdFormat, PasswdHashAlgo, Wordlist, hash_passwd};
 
 const UPPER: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
+const EFF_LARGE_WORDLIST: &str = include_str!("../../assets/eff_large_wordlist.txt");
+
+/// 密码生成规则，可以从 YAML 文件加载，例如：
+///
+/// ```yaml
+/// length: 20
+/// uppercase: true
+/// symbols: "-_.+"
+/// exclude: "il1O0"
+/// min-digits: 2
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PasswdPolicy {
+    pub length: usize,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub number: bool,
+    pub symbol: bool,
+    /// 自定义字符集，替换内置的大写、小写、数字和符号
+    pub charset: Option<String>,
+    /// 自定义符号集，替换 `SYMBOL`
+    pub symbols: Option<String>,
+    /// 不允许出现的字符
+    pub exclude: String,
+    pub min_upper: usize,
+    pub min_lower: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    /// 不允许相邻的字符重复
+    pub no_repeat: bool,
+}
+
+impl Default for PasswdPolicy {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            uppercase: false,
+            lowercase: true,
+            number: true,
+            symbol: true,
+            charset: None,
+            symbols: None,
+            exclude: String::new(),
+            min_upper: 0,
+            min_lower: 0,
+            min_digits: 0,
+            min_symbols: 0,
+            no_repeat: false,
+        }
+    }
+}
+
+impl PasswdPolicy {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        serde_yaml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid policy {}: {}", path.display(), e))
+    }
+
+    /// 每种字符类型的字符集和最少出现次数，去掉了排除的字符
+    fn char_sets(&self) -> anyhow::Result<Vec<(Vec<u8>, usize)>> {
+        let keep = |chars: &[u8]| {
+            chars
+                .iter()
+                .copied()
+                .filter(|c| !self.exclude.as_bytes().contains(c))
+                .collect::<Vec<_>>()
+        };
+
+        let char_sets = match &self.charset {
+            Some(charset) => {
+                if !charset.is_ascii() {
+                    return Err(anyhow::anyhow!(
+                        "Charset must only contain ASCII characters"
+                    ));
+                }
+                let mut charset = keep(charset.as_bytes());
+                charset.sort();
+                charset.dedup();
+                let class = |f: fn(&u8) -> bool| charset.iter().copied().filter(f).collect();
+                vec![
+                    (class(u8::is_ascii_uppercase), self.min_upper),
+                    (class(u8::is_ascii_lowercase), self.min_lower),
+                    (class(u8::is_ascii_digit), self.min_digits),
+                    (class(|c| !c.is_ascii_alphanumeric()), self.min_symbols),
+                ]
+            }
+            None => {
+                let symbols = self.symbols.as_deref().map_or(SYMBOL, str::as_bytes);
+                // 启用的字符类型至少出现一次，设置了最少次数也会启用该类型
+                [
+                    (self.uppercase, UPPER, self.min_upper),
+                    (self.lowercase, LOWER, self.min_lower),
+                    (self.number, NUMBER, self.min_digits),
+                    (self.symbol, symbols, self.min_symbols),
+                ]
+                .into_iter()
+                .filter(|(enabled, _, min)| *enabled || *min > 0)
+                .map(|(_, chars, min)| (keep(chars), min.max(1)))
+                .collect()
+            }
+        };
+
+        if let Some((_, min)) = char_sets
+            .iter()
+            .find(|(chars, min)| chars.is_empty() && *min > 0)
+        {
+            return Err(anyhow::anyhow!(
+                "{} character(s) required from a character type with no characters left",
+                min
+            ));
+        }
+        Ok(char_sets
+            .into_iter()
+            .filter(|(chars, _)| !chars.is_empty())
+            .collect())
+    }
+
+    /// 密码的熵（比特）：长度 × log2(字符集大小)
+    pub fn entropy(&self) -> anyhow::Result<f64> {
+        let charset_len: usize = self.char_sets()?.iter().map(|(chars, _)| chars.len()).sum();
+        if charset_len == 0 {
+            return Ok(0.0);
+        }
+        Ok(self.length as f64 * (charset_len as f64).log2())
+    }
+}
 
 pub fn process_passwd(
     length: usize,
@@ -12,73 +151,261 @@ pub fn process_passwd(
     number: bool,
     symbol: bool,
 ) -> anyhow::Result<Vec<u8>> {
-    let mut rng = rand::thread_rng();
+    let policy = PasswdPolicy {
+        length,
+        uppercase,
+        lowercase,
+        number,
+        symbol,
+        ..Default::default()
+    };
+    process_passwd_with_policy(&policy)
+}
 
-    let required_char_types = [uppercase, lowercase, number, symbol]
+pub fn process_passwd_with_policy(policy: &PasswdPolicy) -> anyhow::Result<Vec<u8>> {
+    process_passwd_with_rng(policy, &mut rand::thread_rng())
+}
+
+/// 使用指定的随机数生成器，传入固定种子的 RNG 可以得到可复现的密码。
+/// 生成真实密码时必须使用密码学安全的 RNG，例如 `thread_rng()`
+pub fn process_passwd_with_rng<R: Rng + ?Sized>(
+    policy: &PasswdPolicy,
+    rng: &mut R,
+) -> anyhow::Result<Vec<u8>> {
+    let char_sets = policy.char_sets()?;
+    let all_chars = char_sets
         .iter()
-        .filter(|&&enabled| enabled)
-        .count();
+        .flat_map(|(chars, _)| chars.iter().copied())
+        .collect::<Vec<_>>();
+
+    if all_chars.is_empty() {
+        return Err(anyhow::anyhow!(
+            "At least one character type must be enabled"
+        ));
+    }
 
-    if length < required_char_types {
+    let required = char_sets.iter().map(|(_, min)| min).sum::<usize>();
+    if policy.length < required {
         return Err(anyhow::anyhow!(
             "Password length must be at least {}",
-            required_char_types
+            required
+        ));
+    }
+    if policy.no_repeat && all_chars.len() < 2 && policy.length > 1 {
+        return Err(anyhow::anyhow!(
+            "At least 2 characters are needed to avoid repeats"
         ));
     }
 
-    let mut all_chars = Vec::new();
-    let mut char_sets = Vec::new();
+    let mut passwd = Vec::with_capacity(policy.length);
 
-    if uppercase {
-        all_chars.extend_from_slice(UPPER);
-        char_sets.push(UPPER);
+    // 确保每种字符类型满足最少出现次数
+    for (chars, min) in &char_sets {
+        for _ in 0..*min {
+            let c = chars
+                .iter()
+                .choose(rng)
+                .expect("Character set should not be empty");
+            passwd.push(*c);
+        }
     }
 
-    if lowercase {
-        all_chars.extend_from_slice(LOWER);
-        char_sets.push(LOWER);
-    }
+    // 用随机字符填充密码的剩余部分
+    for _ in passwd.len()..policy.length {
+        let c = all_chars
+            .iter()
+            .choose(rng)
+            .expect("all_chars should not be empty");
 
-    if number {
-        all_chars.extend_from_slice(NUMBER);
-        char_sets.push(NUMBER);
+        passwd.push(*c);
     }
 
-    if symbol {
-        all_chars.extend_from_slice(SYMBOL);
-        char_sets.push(SYMBOL);
-    }
+    // 随机排列密码中的字符
+    passwd.shuffle(rng);
 
-    if all_chars.is_empty() {
+    // 把和前一个字符相同的字符与其他位置交换，直到没有相邻的重复
+    if policy.no_repeat {
+        for _ in 0..1000 {
+            let Some(i) = (1..passwd.len()).find(|&i| passwd[i] == passwd[i - 1]) else {
+                return Ok(passwd);
+            };
+            let j = rng.gen_range(0..passwd.len());
+            passwd.swap(i, j);
+        }
         return Err(anyhow::anyhow!(
-            "At least one character type must be enabled"
+            "Could not avoid repeated characters, use a larger character set"
         ));
     }
 
-    let mut passwd = Vec::with_capacity(length);
+    Ok(passwd)
+}
 
-    // 确保每种启用的字符类型至少出现一次
-    for char_set in &char_sets {
-        let c = char_set
-            .iter()
-            .choose(&mut rng)
-            .expect("Character set should not be empty");
+/// 读取词表，每行一个单词，兼容 EFF 词表 `11111\tabacus` 的格式
+pub fn load_wordlist(wordlist: &Wordlist) -> anyhow::Result<Vec<String>> {
+    let content = match wordlist {
+        Wordlist::EffLarge => EFF_LARGE_WORDLIST.to_string(),
+        Wordlist::File(path) => fs::read_to_string(path)?,
+    };
+    let mut words = content
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(String::from)
+        .collect::<Vec<_>>();
+    // 重复的单词不会增加熵
+    words.sort();
+    words.dedup();
 
-        passwd.push(*c);
+    if words.len() < 2 {
+        return Err(anyhow::anyhow!("Wordlist must contain at least 2 words"));
     }
+    Ok(words)
+}
 
-    // 用随机字符填充密码的剩余部分
-    for _ in passwd.len()..length {
-        let c = all_chars
-            .iter()
-            .choose(&mut rng)
-            .expect("all_chars should not be empty");
+pub fn process_passphrase(
+    wordlist: &[String],
+    words: usize,
+    separator: &str,
+    capitalize: bool,
+    number: bool,
+    symbol: bool,
+) -> anyhow::Result<String> {
+    process_passphrase_with_rng(
+        wordlist,
+        words,
+        separator,
+        capitalize,
+        number,
+        symbol,
+        &mut rand::thread_rng(),
+    )
+}
 
-        passwd.push(*c);
+/// 和 `process_passwd_with_rng` 一样，随机数生成器由调用方决定
+pub fn process_passphrase_with_rng<R: Rng + ?Sized>(
+    wordlist: &[String],
+    words: usize,
+    separator: &str,
+    capitalize: bool,
+    number: bool,
+    symbol: bool,
+    rng: &mut R,
+) -> anyhow::Result<String> {
+    if words == 0 {
+        return Err(anyhow::anyhow!("Passphrase must have at least 1 word"));
     }
 
-    // 随机排列密码中的字符
-    passwd.shuffle(&mut rng);
+    let mut passphrase = (0..words)
+        .map(|_| wordlist.choose(rng).expect("Wordlist should not be empty"))
+        .map(|word| {
+            if capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.clone()
+            }
+        })
+        .collect::<Vec<String>>();
 
-    Ok(passwd)
+    // 在随机的单词后面追加数字和符号
+    for (enabled, char_set) in [(number, NUMBER), (symbol, SYMBOL)] {
+        if enabled {
+            let c = char_set
+                .iter()
+                .choose(rng)
+                .expect("Character set should not be empty");
+            let i = rng.gen_range(0..passphrase.len());
+            passphrase[i].push(*c as char);
+        }
+    }
+
+    Ok(passphrase.join(separator))
+}
+
+/// 口令的熵（比特），只计算随机选择的部分，大写是固定规则不增加熵
+pub fn passphrase_entropy(wordlist_len: usize, words: usize, number: bool, symbol: bool) -> f64 {
+    let mut entropy = words as f64 * (wordlist_len as f64).log2();
+    if number {
+        entropy += (NUMBER.len() as f64).log2();
+    }
+    if symbol {
+        entropy += (SYMBOL.len() as f64).log2();
+    }
+    entropy
+}
+
+/// 批量生成的一条记录，`username` 和 `hash` 是可选的列
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PasswdRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// 把密码和用户名配对，并按需计算哈希；哈希计算很慢，所以并行进行
+pub fn process_passwd_records(
+    passwords: Vec<String>,
+    usernames: Option<Vec<String>>,
+    hash: Option<PasswdHashAlgo>,
+) -> anyhow::Result<Vec<PasswdRecord>> {
+    let usernames = match usernames {
+        Some(usernames) if usernames.len() != passwords.len() => {
+            return Err(anyhow::anyhow!(
+                "Got {} usernames for {} passwords",
+                usernames.len(),
+                passwords.len()
+            ));
+        }
+        Some(usernames) => usernames.into_iter().map(Some).collect(),
+        None => vec![None; passwords.len()],
+    };
+
+    passwords
+        .into_par_iter()
+        .zip(usernames)
+        .map(|(password, username)| {
+            let hash = hash.map(|algo| hash_passwd(&password, algo)).transpose()?;
+            Ok(PasswdRecord {
+                username,
+                password,
+                hash,
+            })
+        })
+        .collect()
+}
+
+pub fn format_passwd_records(
+    records: &[PasswdRecord],
+    format: PasswdFormat,
+) -> anyhow::Result<String> {
+    let content = match format {
+        PasswdFormat::Plain => records
+            .iter()
+            .map(|record| {
+                [
+                    record.username.as_deref(),
+                    Some(record.password.as_str()),
+                    record.hash.as_deref(),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("\t")
+            })
+            .map(|line| line + "\n")
+            .collect(),
+        PasswdFormat::Json => serde_json::to_string_pretty(records)? + "\n",
+        PasswdFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(Vec::new());
+            for record in records {
+                wtr.serialize(record)?;
+            }
+            String::from_utf8(wtr.into_inner()?)?
+        }
+    };
+    Ok(content)
 }
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use argon2::{
+    Argon2,
+    password_hash::{
+        self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng,
+    },
+};
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+use sha_crypt::{ROUNDS_DEFAULT, Sha256Params, Sha512Params};
+
+use crate::{PasswdHashAlgo, secure_eq};
+
+/// Hash `password` for storage, as a PHC string (`$argon2id$...`) or in the
+/// modular crypt format bcrypt uses (`$2b$...`).
+pub fn hash_passwd(password: &str, algo: PasswdHashAlgo) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = match algo {
+        PasswdHashAlgo::Argon2id => Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string()),
+        PasswdHashAlgo::Scrypt => Scrypt
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string()),
+        PasswdHashAlgo::Pbkdf2Sha256 => Pbkdf2
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string()),
+        PasswdHashAlgo::Bcrypt => return Ok(bcrypt::hash(password, bcrypt::DEFAULT_COST)?),
+    };
+    hash.map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
+}
+
+/// Verify a `$5$[rounds=N$]salt$hash` or `$6$...` sha-crypt hash.
+fn verify_sha_crypt(password: &str, hash: &str) -> Result<bool> {
+    let invalid = |_| anyhow::anyhow!("Invalid sha-crypt hash");
+    let mut fields = hash.split('$').skip(1);
+    let id = fields.next();
+    let mut field = fields.next();
+    let rounds = match field.and_then(|f| f.strip_prefix("rounds=")) {
+        Some(rounds) => {
+            field = fields.next();
+            rounds.parse().map_err(|_| invalid(()))?
+        }
+        None => ROUNDS_DEFAULT,
+    };
+    let (Some(salt), Some(expected), None) = (field, fields.next(), fields.next()) else {
+        return Err(invalid(()));
+    };
+
+    let password = password.as_bytes();
+    let salt = salt.as_bytes();
+    let computed = match id {
+        Some("5") => Sha256Params::new(rounds)
+            .and_then(|params| sha_crypt::sha256_crypt_b64(password, salt, &params)),
+        _ => Sha512Params::new(rounds)
+            .and_then(|params| sha_crypt::sha512_crypt_b64(password, salt, &params)),
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid sha-crypt hash: {:?}", e))?;
+    Ok(secure_eq(computed, expected))
+}
+
+/// Check `password` against a PHC string (argon2, scrypt, pbkdf2) or a modular
+/// crypt hash (bcrypt `$2b$`, sha-crypt `$5$`/`$6$`) as found in `/etc/shadow`.
+pub fn verify_passwd(password: &str, hash: &str) -> Result<bool> {
+    let hash = hash.trim();
+    if ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+    {
+        return Ok(bcrypt::verify(password, hash)?);
+    }
+    if hash.starts_with("$5$") || hash.starts_with("$6$") {
+        return verify_sha_crypt(password, hash);
+    }
+
+    let hash =
+        PasswordHash::new(hash).map_err(|e| anyhow::anyhow!("Invalid password hash: {}", e))?;
+    let argon2 = Argon2::default();
+    let verifier: &dyn PasswordVerifier = match hash.algorithm.as_str() {
+        "argon2id" | "argon2i" | "argon2d" => &argon2,
+        "scrypt" => &Scrypt,
+        "pbkdf2" | "pbkdf2-sha256" | "pbkdf2-sha512" => &Pbkdf2,
+        algorithm => {
+            return Err(anyhow::anyhow!(
+                "Unsupported password hash algorithm: {}",
+                algorithm
+            ));
+        }
+    };
+    match verifier.verify_password(password.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(anyhow::anyhow!("Invalid password hash: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_verify_passwd() -> Result<()> {
+        // pbkdf2 with its default 600k rounds takes seconds in debug builds
+        for algo in [
+            PasswdHashAlgo::Argon2id,
+            PasswdHashAlgo::Bcrypt,
+            PasswdHashAlgo::Scrypt,
+        ] {
+            let hash = hash_passwd("hunter2", algo)?;
+            assert!(verify_passwd("hunter2", &hash)?, "{}", hash);
+            assert!(!verify_passwd("hunter3", &hash)?, "{}", hash);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_legacy_hashes() -> Result<()> {
+        // generated with `openssl passwd -6 -salt saltsalt hunter2` (and -5)
+        let sha512 = "$6$saltsalt$8iYtNHxjWRl.NF6oNZ5tF.iKFlQREaXBLlSmZKP6dy9l5z3vsooWNW0/GZ6Nej73/TFug6pIPSqbJoCT6dfnj.";
+        let sha256 = "$5$saltsalt$OIdfjX.u4Y3SJ4I2bX8w5BMf1VAUhHABNUirScDzZi3";
+        assert!(verify_passwd("hunter2", sha512)?);
+        assert!(!verify_passwd("hunter3", sha512)?);
+        assert!(verify_passwd("hunter2", sha256)?);
+
+        let salt = SaltString::generate(&mut OsRng);
+        let params = pbkdf2::Params {
+            rounds: 1000,
+            ..Default::default()
+        };
+        let pbkdf2 = Pbkdf2
+            .hash_password_customized(b"hunter2", None, None, params, &salt)
+            .unwrap()
+            .to_string();
+        assert!(pbkdf2.starts_with("$pbkdf2-sha256$i=1000,"));
+        assert!(verify_passwd("hunter2", &pbkdf2)?);
+        assert!(verify_passwd("hunter2", "$1$saltsalt$abc").is_err());
+        Ok(())
+    }
+}
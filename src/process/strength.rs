@@ -0,0 +1,141 @@
+use std::fmt;
+
+use zxcvbn::{matching::patterns::MatchPattern, zxcvbn};
+
+#[derive(Debug, Clone)]
+pub struct PasswordStrength {
+    /// 0 (too guessable) to 4 (very unguessable)
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub crack_times: Vec<(&'static str, String)>,
+    /// Weak patterns found in the password with the matching token
+    pub patterns: Vec<(&'static str, String)>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+fn score_label(score: u8) -> &'static str {
+    match score {
+        0 => "very weak",
+        1 => "weak",
+        2 => "fair",
+        3 => "strong",
+        _ => "very strong",
+    }
+}
+
+fn pattern_name(pattern: &MatchPattern) -> Option<&'static str> {
+    let name = match pattern {
+        MatchPattern::Dictionary(p) if p.reversed => "reversed word",
+        MatchPattern::Dictionary(p) if p.l33t => "l33t word",
+        MatchPattern::Dictionary(_) => "dictionary word",
+        MatchPattern::Spatial(_) => "keyboard walk",
+        MatchPattern::Repeat(_) => "repeat",
+        MatchPattern::Sequence(_) => "sequence",
+        MatchPattern::Regex(_) => "year",
+        MatchPattern::Date(_) => "date",
+        MatchPattern::BruteForce => return None,
+    };
+    Some(name)
+}
+
+impl fmt::Display for PasswordStrength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "score:       {}/4 ({})",
+            self.score,
+            score_label(self.score)
+        )?;
+        writeln!(f, "guesses:     10^{:.1}", self.guesses_log10)?;
+        writeln!(f, "crack time:")?;
+        for (scenario, time) in &self.crack_times {
+            writeln!(f, "  {:<32} {}", scenario, time)?;
+        }
+        if !self.patterns.is_empty() {
+            writeln!(f, "patterns:")?;
+            for (name, token) in &self.patterns {
+                writeln!(f, "  {:<32} {}", name, token)?;
+            }
+        }
+        if let Some(warning) = &self.warning {
+            writeln!(f, "warning:     {}", warning)?;
+        }
+        if !self.suggestions.is_empty() {
+            writeln!(f, "suggestions:")?;
+            for suggestion in &self.suggestions {
+                writeln!(f, "  - {}", suggestion)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Estimate how hard `password` is to guess. `user_inputs` are words like the
+/// user or site name that an attacker would try first.
+pub fn process_passwd_check(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let entropy = zxcvbn(password, user_inputs);
+    let times = entropy.crack_times();
+    let crack_times = vec![
+        (
+            "online, throttled (100/hour)",
+            times.online_throttling_100_per_hour().to_string(),
+        ),
+        (
+            "online, unthrottled (10/s)",
+            times.online_no_throttling_10_per_second().to_string(),
+        ),
+        (
+            "offline, slow hash (1e4/s)",
+            times.offline_slow_hashing_1e4_per_second().to_string(),
+        ),
+        (
+            "offline, fast hash (1e10/s)",
+            times.offline_fast_hashing_1e10_per_second().to_string(),
+        ),
+    ];
+    let patterns = entropy
+        .sequence()
+        .iter()
+        .filter_map(|m| pattern_name(&m.pattern).map(|name| (name, m.token.clone())))
+        .collect();
+    let (warning, suggestions) = match entropy.feedback() {
+        Some(feedback) => (
+            feedback.warning().map(|w| w.to_string()),
+            feedback
+                .suggestions()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        ),
+        None => (None, Vec::new()),
+    };
+
+    PasswordStrength {
+        score: entropy.score().into(),
+        guesses_log10: entropy.guesses_log10(),
+        crack_times,
+        patterns,
+        warning,
+        suggestions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_passwd_check() {
+        let weak = process_passwd_check("qwerty2019", &[]);
+        assert!(weak.score <= 1);
+        assert!(weak.patterns.contains(&("year", "2019".into())));
+        assert!(!weak.suggestions.is_empty());
+
+        let named = process_passwd_check("alice1988", &["alice"]);
+        assert!(named.score <= 1);
+
+        let strong = process_passwd_check("correct-Horse-battery-staple-17!", &[]);
+        assert_eq!(strong.score, 4);
+    }
+}
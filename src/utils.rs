@@ -1,7 +1,8 @@
 use std::{
     fs::File,
-    io::{Cursor, Read},
+    io::{self, BufWriter, Cursor, IsTerminal, Read, Write},
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 pub fn verify_input_file(input: &str) -> Result<String, &'static str> {
@@ -22,21 +23,110 @@ pub fn verify_path(path: &str) -> Result<PathBuf, &'static str> {
     }
 }
 
-pub fn input_reader(input: &str) -> anyhow::Result<String> {
-    let mut rdr: Box<dyn Read> = if input == "-" {
-        // 从标准输入读取
-        Box::new(std::io::stdin())
-    } else if Path::new(input).exists() && Path::new(input).is_file() {
-        // 从文件读取
-        Box::new(File::open(input)?)
-    } else {
-        // 直接将输入字符串作为数据源
-        Box::new(Cursor::new(input))
+/// Where a command reads its data from. Content is always handled as raw
+/// bytes so binary files survive untouched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+    Literal(Vec<u8>),
+    Env(String),
+}
+
+/// Parses `-` as stdin and anything else as a file path, optionally written
+/// as `@path`. Literal content is never guessed from a path that happens not
+/// to exist, it has to be passed explicitly (see `--text`).
+impl FromStr for Input {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Input::Stdin);
+        }
+        let path = Path::new(s.strip_prefix('@').unwrap_or(s));
+        if path.is_file() {
+            Ok(Input::File(path.into()))
+        } else {
+            Err(anyhow::anyhow!(
+                "File {} does not exist, use --text to pass literal content",
+                path.display()
+            ))
+        }
+    }
+}
+
+pub fn parse_input(input: &str) -> anyhow::Result<Input> {
+    input.parse()
+}
+
+impl Input {
+    pub fn reader(&self) -> anyhow::Result<Box<dyn Read>> {
+        let rdr: Box<dyn Read> = match self {
+            Input::Stdin => Box::new(io::stdin()),
+            Input::File(path) => Box::new(File::open(path)?),
+            Input::Literal(content) => Box::new(Cursor::new(content.clone())),
+            Input::Env(name) => {
+                let content = std::env::var(name)
+                    .map_err(|e| anyhow::anyhow!("Environment variable {}: {}", name, e))?;
+                Box::new(Cursor::new(content.into_bytes()))
+            }
+        };
+        Ok(rdr)
+    }
+
+    /// Read the whole input. Bytes are returned exactly as they are unless
+    /// `trim` asks for leading and trailing whitespace to be stripped.
+    pub fn read(&self, trim: bool) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.reader()?.read_to_end(&mut buf)?;
+        if trim {
+            buf = buf.trim_ascii().to_vec();
+        }
+        Ok(buf)
+    }
+}
+
+/// A buffered writer to the file at `output`, or to stdout if there is none.
+pub fn output_writer(output: Option<&Path>) -> anyhow::Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
     };
+    Ok(writer)
+}
+
+/// Write `data` to the file at `output`, or to stdout if there is none.
+pub fn write_output(output: Option<&Path>, data: &[u8]) -> anyhow::Result<()> {
+    let mut writer = output_writer(output)?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
 
-    let mut buf = String::new();
-    rdr.read_to_string(&mut buf)?;
-    let buf = buf.trim();
+/// Prompt for a password without echoing it when stdin is a terminal,
+/// otherwise read the first line of stdin.
+pub fn read_password(prompt: &str) -> anyhow::Result<String> {
+    if io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password(prompt)?);
+    }
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Percent-encode everything but the RFC 3986 unreserved characters.
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
 
-    Ok(buf.to_string())
+/// Compare secrets in constant time by comparing their blake3 hashes.
+pub fn secure_eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    blake3::hash(a.as_ref()) == blake3::hash(b.as_ref())
 }
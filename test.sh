@@ -2,4 +2,3 @@ curl http://localhost:8080/
 curl http://localhost:8080/fixtures/blake3.txt
 curl http://localhost:8080/Cargo.toml
 curl http://localhost:8080/rustlang.txt
-curl http://localhost:8080/tower/Cargo.toml
@@ -0,0 +1,356 @@
+use std::fs;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use rcli::{Ed25519, Ed25519Verifier, HttpAuth, HttpOptions, KeyLoader, http_router, sign_url};
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+/// A served `root` directory next to a `secret` file that must stay out of reach.
+fn setup() -> anyhow::Result<TempDir> {
+    let tmp = tempfile::tempdir()?;
+    let root = tmp.path().join("root");
+    fs::create_dir_all(root.join("docs"))?;
+    fs::write(root.join("docs/readme.txt"), "hello")?;
+    fs::write(root.join(".env"), "API_KEY=123")?;
+    fs::write(tmp.path().join("secret"), "top secret")?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(tmp.path().join("secret"), root.join("escape"))?;
+        std::os::unix::fs::symlink(root.join("docs/readme.txt"), root.join("inside"))?;
+    }
+    Ok(tmp)
+}
+
+fn build_router(tmp: &TempDir, options: HttpOptions) -> anyhow::Result<Router> {
+    http_router(tmp.path().join("root"), options)
+}
+
+async fn send(router: &Router, request: Request<Body>) -> anyhow::Result<(StatusCode, String)> {
+    let response = router.clone().oneshot(request).await?;
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await?;
+    Ok((status, String::from_utf8_lossy(&body).into_owned()))
+}
+
+async fn get(router: &Router, uri: &str) -> anyhow::Result<(StatusCode, String)> {
+    send(router, Request::get(uri).body(Body::empty())?).await
+}
+
+#[tokio::test]
+async fn test_http_rejects_path_traversal() -> anyhow::Result<()> {
+    let tmp = setup()?;
+    let router = build_router(&tmp, HttpOptions::default())?;
+
+    let (status, body) = get(&router, "/docs/readme.txt").await?;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "hello"));
+
+    for uri in [
+        "/../secret",
+        "/docs/../../secret",
+        "/%2e%2e/secret",
+        "/docs/%2E%2E%2F%2E%2E%2Fsecret",
+        "/..%5csecret",
+        "/docs/..%2f..%2fsecret",
+    ] {
+        let (status, body) = get(&router, uri).await?;
+        assert_ne!(status, StatusCode::OK, "{}", uri);
+        assert!(!body.contains("top secret"), "{}", uri);
+    }
+
+    // an absolute path is looked up below the root, not at the file system root
+    let secret = tmp.path().join("secret");
+    let (status, _) = get(&router, &format!("/{}", secret.display())).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(&router, "//etc/passwd").await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_http_symlink_policy() -> anyhow::Result<()> {
+    let tmp = setup()?;
+
+    let router = build_router(&tmp, HttpOptions::default())?;
+    let (status, _) = get(&router, "/escape").await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = get(&router, "/inside").await?;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "hello"));
+    let (_, listing) = get(&router, "/").await?;
+    assert!(listing.contains("inside") && !listing.contains("escape"));
+
+    let options = HttpOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+    let (status, body) = get(&router, "/escape").await?;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "top secret"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_dotfile_policy() -> anyhow::Result<()> {
+    let tmp = setup()?;
+
+    let router = build_router(&tmp, HttpOptions::default())?;
+    for uri in ["/.env", "/docs/../.env", "/%2eenv"] {
+        let (status, _) = get(&router, uri).await?;
+        assert_ne!(status, StatusCode::OK, "{}", uri);
+    }
+    let (_, listing) = get(&router, "/").await?;
+    assert!(!listing.contains(".env"));
+
+    let options = HttpOptions {
+        show_hidden: true,
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+    let (status, body) = get(&router, "/.env").await?;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "API_KEY=123"));
+
+    Ok(())
+}
+
+fn multipart(uri: &str, file_name: &str, content: &str) -> anyhow::Result<Request<Body>> {
+    let body = format!(
+        "--XBOUNDARY\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n\
+         {}\r\n\
+         --XBOUNDARY--\r\n",
+        file_name, content
+    );
+    Ok(Request::post(uri)
+        .header("content-type", "multipart/form-data; boundary=XBOUNDARY")
+        .body(Body::from(body))?)
+}
+
+fn put(uri: &str, content: &'static str) -> anyhow::Result<Request<Body>> {
+    Ok(Request::put(uri).body(Body::from(content))?)
+}
+
+#[tokio::test]
+async fn test_http_upload() -> anyhow::Result<()> {
+    let tmp = setup()?;
+    let root = tmp.path().join("root");
+
+    let router = build_router(&tmp, HttpOptions::default())?;
+    let (status, _) = send(&router, put("/docs/new.txt", "hi")?).await?;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+    let options = HttpOptions {
+        upload: true,
+        max_upload_size: 16,
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+    let (_, listing) = get(&router, "/docs/").await?;
+    assert!(listing.contains(r#"enctype="multipart/form-data""#));
+
+    let (status, _) = send(&router, multipart("/docs", "a.txt", "from a form")?).await?;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(fs::read_to_string(root.join("docs/a.txt"))?, "from a form");
+
+    // the client's directories are dropped from the name
+    let (status, _) = send(&router, multipart("/", "../../evil.txt", "x")?).await?;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(root.join("evil.txt").exists());
+    assert!(!tmp.path().join("evil.txt").exists());
+
+    let (status, _) = send(&router, put("/docs/b.txt", "put")?).await?;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(root.join("docs/b.txt"))?, "put");
+
+    let (status, _) = send(&router, put("/docs/b.txt", "again")?).await?;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // racing uploads of a new name: one wins, the other never replaces it
+    let (first, second) = tokio::join!(
+        send(&router, put("/docs/race.txt", "first")?),
+        send(&router, put("/docs/race.txt", "second")?),
+    );
+    let mut statuses = [first?.0, second?.0];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+    let (status, _) = send(&router, put("/docs/big.bin", "more than sixteen bytes")?).await?;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(!root.join("docs/big.bin").exists());
+
+    for uri in [
+        "/../escaped.txt",
+        "/docs/%2e%2e%2f%2e%2e%2fescaped.txt",
+        "/.env",
+    ] {
+        let (status, _) = send(&router, put(uri, "x")?).await?;
+        assert!(status.is_client_error(), "{}", uri);
+    }
+    assert!(!tmp.path().join("escaped.txt").exists());
+    assert_eq!(fs::read_to_string(root.join(".env"))?, "API_KEY=123");
+
+    let options = HttpOptions {
+        upload: true,
+        overwrite: true,
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+    let (status, _) = send(&router, put("/docs/b.txt", "again")?).await?;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(fs::read_to_string(root.join("docs/b.txt"))?, "again");
+
+    Ok(())
+}
+
+fn with_auth(uri: &str, authorization: &str) -> anyhow::Result<Request<Body>> {
+    Ok(Request::get(uri)
+        .header("authorization", authorization)
+        .body(Body::empty())?)
+}
+
+fn basic(user: &str, password: &str) -> String {
+    format!(
+        "Basic {}",
+        BASE64_STANDARD.encode(format!("{}:{}", user, password))
+    )
+}
+
+#[tokio::test]
+async fn test_http_auth() -> anyhow::Result<()> {
+    let tmp = setup()?;
+    let htpasswd = tmp.path().join("htpasswd");
+    fs::write(&htpasswd, format!("bob:{}\n", bcrypt::hash("hunter2", 4)?))?;
+    let auth = HttpAuth::default()
+        .user("alice", "wonderland")
+        .htpasswd(&htpasswd)?
+        .token("s3cr3t")
+        .url_key(Ed25519Verifier::load("fixtures/ed25519.pk")?);
+    let options = HttpOptions {
+        auth,
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+
+    let response = router
+        .clone()
+        .oneshot(Request::get("/docs/readme.txt").body(Body::empty())?)
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let challenges = response
+        .headers()
+        .get_all("www-authenticate")
+        .iter()
+        .count();
+    assert_eq!(challenges, 2);
+
+    for authorization in [
+        basic("alice", "wonderland"),
+        basic("bob", "hunter2"),
+        "Bearer s3cr3t".to_string(),
+    ] {
+        let request = with_auth("/docs/readme.txt", &authorization)?;
+        let (status, body) = send(&router, request).await?;
+        assert_eq!(
+            (status, body.as_str()),
+            (StatusCode::OK, "hello"),
+            "{}",
+            authorization
+        );
+    }
+    for authorization in [
+        basic("alice", "hunter2"),
+        basic("bob", "wonderland"),
+        basic("mallory", ""),
+        "Bearer wrong".to_string(),
+        "Basic !!!".to_string(),
+    ] {
+        let (status, _) = send(&router, with_auth("/", &authorization)?).await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", authorization);
+    }
+
+    let key = Ed25519::load("fixtures/ed25519.sk")?;
+    let expires = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let url = sign_url(&key, "/docs/readme.txt", expires + 60)?;
+    let (status, body) = get(&router, &url).await?;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "hello"));
+
+    // a link only opens what it was signed for, and only until it expires
+    let (status, _) = get(&router, &url.replace("readme.txt", "other.txt")).await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let expired = sign_url(&key, "/docs/readme.txt", expires - 1)?;
+    let (status, _) = get(&router, &expired).await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_cors_and_compression() -> anyhow::Result<()> {
+    let tmp = setup()?;
+    fs::write(tmp.path().join("root/docs/long.txt"), "hello ".repeat(100))?;
+    let options = HttpOptions {
+        auth: HttpAuth::default().token("s3cr3t"),
+        cors: vec!["https://app.example".into()],
+        ..Default::default()
+    };
+    let router = build_router(&tmp, options)?;
+
+    // preflight requests come without credentials
+    let preflight = Request::options("/docs/long.txt")
+        .header("origin", "https://app.example")
+        .header("access-control-request-method", "GET")
+        .header("access-control-request-headers", "authorization")
+        .body(Body::empty())?;
+    let response = router.clone().oneshot(preflight).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example"
+    );
+
+    let request = |origin: &str, range: Option<&str>| {
+        let builder = Request::get("/docs/long.txt")
+            .header("authorization", "Bearer s3cr3t")
+            .header("origin", origin)
+            .header("accept-encoding", "gzip");
+        let builder = match range {
+            Some(range) => builder.header("range", range),
+            None => builder,
+        };
+        builder.body(Body::empty())
+    };
+    let response = router
+        .clone()
+        .oneshot(request("https://app.example", None)?)
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await?;
+    assert!(body.len() < 600);
+
+    let response = router
+        .clone()
+        .oneshot(request("https://evil.example", Some("bytes=0-99"))?)
+        .await?;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert!(!response.headers().contains_key("content-encoding"));
+    assert!(
+        !response
+            .headers()
+            .contains_key("access-control-allow-origin")
+    );
+
+    Ok(())
+}
@@ -1,5 +1,11 @@
 use anyhow::Ok;
-use rcli::process_passwd;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rcli::{
+    PasswdFormat, PasswdHashAlgo, PasswdPolicy, Wordlist, format_passwd_records, load_wordlist,
+    passphrase_entropy, process_passphrase, process_passphrase_with_rng, process_passwd,
+    process_passwd_records, process_passwd_with_policy, process_passwd_with_rng,
+};
 
 #[test]
 fn test_process_passwd_generating() -> anyhow::Result<()> {
@@ -19,3 +25,101 @@ fn test_process_passwd_generating() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_process_passwd_seeded() -> anyhow::Result<()> {
+    let policy = PasswdPolicy::load("fixtures/policy.yaml")?;
+    let generate = |seed| process_passwd_with_rng(&policy, &mut ChaCha20Rng::seed_from_u64(seed));
+    assert_eq!(generate(42)?, generate(42)?);
+    assert_ne!(generate(42)?, generate(43)?);
+
+    // every seed must satisfy the policy, not just the lucky ones
+    for seed in 0..200 {
+        let passwd = generate(seed)?;
+        assert!(passwd.iter().filter(|c| c.is_ascii_uppercase()).count() >= 2);
+        assert!(passwd.iter().filter(|c| c.is_ascii_digit()).count() >= 2);
+        assert!(passwd.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    let wordlist = load_wordlist(&Wordlist::EffLarge)?;
+    let passphrase = |seed| {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        process_passphrase_with_rng(&wordlist, 4, "-", false, true, true, &mut rng)
+    };
+    assert_eq!(passphrase(7)?, passphrase(7)?);
+    // ChaCha20 output is fixed, a seed keeps giving the same password
+    assert_eq!(String::from_utf8(generate(42)?)?, "bZK_83yXDWvG-.93aKdb");
+    assert_eq!(passphrase(7)?, "compacted-anagram$-subsiding7-numbing");
+
+    Ok(())
+}
+
+#[test]
+fn test_process_passphrase_generating() -> anyhow::Result<()> {
+    let wordlist = load_wordlist(&Wordlist::EffLarge)?;
+    assert_eq!(wordlist.len(), 7776);
+
+    let passphrase = process_passphrase(&wordlist, 6, " ", true, true, false)?;
+    let words = passphrase.split(' ').collect::<Vec<_>>();
+    assert_eq!(words.len(), 6);
+    assert!(
+        words
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_ascii_uppercase()))
+    );
+    assert_eq!(passphrase.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+
+    let entropy = passphrase_entropy(wordlist.len(), 6, false, false);
+    assert!((entropy - 77.55).abs() < 0.01);
+
+    Ok(())
+}
+
+#[test]
+fn test_process_passwd_with_policy() -> anyhow::Result<()> {
+    let policy = PasswdPolicy::load("fixtures/policy.yaml")?;
+    assert_eq!(policy.length, 20);
+
+    let passwd = String::from_utf8(process_passwd_with_policy(&policy)?)?;
+    assert_eq!(passwd.len(), 20);
+    assert!(passwd.chars().filter(|c| c.is_ascii_uppercase()).count() >= 2);
+    assert!(passwd.chars().filter(|c| c.is_ascii_digit()).count() >= 2);
+    assert!(passwd.chars().any(|c| "-_.+".contains(c)));
+    assert!(!passwd.chars().any(|c| "il1O0!@#$%^&*".contains(c)));
+    assert!(passwd.as_bytes().windows(2).all(|w| w[0] != w[1]));
+
+    let policy = PasswdPolicy {
+        length: 3,
+        charset: Some("ab".into()),
+        min_digits: 1,
+        ..Default::default()
+    };
+    assert!(process_passwd_with_policy(&policy).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_passwd_records() -> anyhow::Result<()> {
+    let records = process_passwd_records(
+        vec!["s3cret".into(), "hunter2".into()],
+        Some(vec!["alice".into(), "bob".into()]),
+        Some(PasswdHashAlgo::Bcrypt),
+    )?;
+    assert!(bcrypt::verify(
+        "hunter2",
+        records[1].hash.as_deref().unwrap()
+    )?);
+
+    let csv = format_passwd_records(&records, PasswdFormat::Csv)?;
+    assert!(csv.starts_with("username,password,hash\nalice,s3cret,$2b$"));
+
+    let records = process_passwd_records(vec!["s3cret".into()], None, None)?;
+    assert_eq!(
+        format_passwd_records(&records, PasswdFormat::Json)?,
+        "[\n  {\n    \"password\": \"s3cret\"\n  }\n]\n"
+    );
+    assert!(process_passwd_records(vec!["s3cret".into()], Some(vec![]), None).is_err());
+
+    Ok(())
+}